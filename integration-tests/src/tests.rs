@@ -48,8 +48,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .transact()
         .await?;
 
+    // only approved minters can mint on the nft contract
+    for minter in [&bob, &charlie] {
+        let _ = owner
+            .call(nft_contract.id(), "add_minter")
+            .args_json(serde_json::json!({"account_id": minter.id()}))
+            .transact()
+            .await?;
+    }
+
     // begin tests
     test_nft_metadata_view(&owner, &nft_contract).await?;
+    test_nft_mint_requires_minter(&owner, &alice, &nft_contract).await?;
     test_nft_mint_call(&owner, &alice, &nft_contract).await?;
     test_nft_approve_call(&bob, &nft_contract, &market_contract).await?;
     test_sell_nft_listed_on_marketplace(&alice, &nft_contract, &market_contract, &bob).await?;
@@ -92,6 +102,62 @@ async fn test_nft_metadata_view(
     Ok(())
 }
 
+async fn test_nft_mint_requires_minter(
+    owner: &Account,
+    user: &Account,
+    contract: &Contract,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let request_payload = json!({
+        "token_id": "0",
+        "token_owner_id": user.id(),
+        "token_metadata": {
+            "title": "Grumpy Cat",
+            "description": "Not amused.",
+            "media": "https://www.adamsdrafting.com/wp-content/uploads/2018/06/More-Grumpy-Cat.jpg"
+        },
+    });
+
+    // the user isn't an approved minter yet
+    let minting_result = user
+        .call(contract.id(), "nft_mint")
+        .args_json(request_payload.clone())
+        .deposit(NearToken::from_yoctonear(helpers::DEFAULT_DEPOSIT))
+        .transact()
+        .await?;
+    assert!(minting_result.is_failure());
+
+    // a minter with no quota left can't mint either
+    let _ = owner
+        .call(contract.id(), "add_minter")
+        .args_json(json!({"account_id": user.id(), "quota": 0}))
+        .transact()
+        .await?;
+    let minting_result = user
+        .call(contract.id(), "nft_mint")
+        .args_json(request_payload)
+        .deposit(NearToken::from_yoctonear(helpers::DEFAULT_DEPOSIT))
+        .transact()
+        .await?;
+    assert!(minting_result.is_failure());
+
+    // approve the user without a quota for the rest of the tests
+    let _ = owner
+        .call(contract.id(), "add_minter")
+        .args_json(json!({"account_id": user.id()}))
+        .transact()
+        .await?;
+    let is_minter: bool = contract
+        .call("is_minter")
+        .args_json(json!({"account_id": user.id()}))
+        .view()
+        .await?
+        .json()?;
+    assert!(is_minter);
+
+    println!("      Passed ✅ test_nft_mint_requires_minter");
+    Ok(())
+}
+
 async fn test_nft_mint_call(
     owner: &Account,
     user: &Account,
@@ -125,6 +191,8 @@ async fn test_nft_mint_call(
         {
             "approved_account_ids": {},
             "royalty": {},
+            "lock": serde_json::Value::Null,
+            "is_valid": true,
             "token_id": "1",
             "owner_id": user.id(),
             "metadata": {
@@ -393,9 +461,9 @@ async fn test_reselling_and_royalties(
             "description": "Not amused.",
             "media": "https://www.adamsdrafting.com/wp-content/uploads/2018/06/More-Grumpy-Cat.jpg"
         },
-        "perpetual_royalties": {
-            user.id().to_string(): 2000 as u128
-        }
+        "perpetual_royalties": [
+            [user.id(), 2000]
+        ]
     });
     let _ = user
         .call(nft_contract.id(), "nft_mint")
@@ -556,10 +624,10 @@ async fn test_royalties_exceeding_100_percents(
             "description": "Not amused.",
             "media": "https://www.adamsdrafting.com/wp-content/uploads/2018/06/More-Grumpy-Cat.jpg"
         },
-        "perpetual_royalties": {
-            user.id().to_string(): 5000 as u128,
-            market_contract.id().to_string(): 6000 as u128
-        }
+        "perpetual_royalties": [
+            [user.id(), 5000],
+            [market_contract.id(), 6000]
+        ]
     });
     let minting_result = user
         .call(nft_contract.id(), "nft_mint")
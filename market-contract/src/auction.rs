@@ -0,0 +1,346 @@
+use crate::*;
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::NearSchema;
+
+//the most bidders an auction can have. Settling refunds every bidder in one call so this is limited by GAS
+pub(crate) const MAX_BIDDERS_PER_AUCTION: usize = 25;
+
+//the timeline of a sale that is listed as a sealed-bid (Vickrey) auction. The sale conditions act as the reserve price
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SealedBidAuction {
+    //when the commit phase ends and the reveal phase starts, Unix epoch in milliseconds
+    pub commit_ends_at: u64,
+    //when the reveal phase ends and the auction can be settled, Unix epoch in milliseconds
+    pub reveal_ends_at: u64,
+}
+
+//a bid that was committed to an auction
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SealedBid {
+    //sha256(bid || salt) where the bid is the amount in yoctoNEAR encoded as 16 little-endian bytes
+    pub commitment: Base64VecU8,
+    //the deposit held in escrow. This must cover the bid once it's revealed
+    pub deposit: NearToken,
+    //the part of the attached deposit that pays for storing the bid. This is refunded when the auction is settled
+    pub storage_deposit: NearToken,
+    //the bid once it has been opened during the reveal phase
+    pub revealed_bid: Option<NearToken>,
+}
+
+//The Json auction is what will be returned from view calls.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonAuction {
+    //the sale that is being auctioned. The sale conditions are the reserve price
+    pub sale: Sale,
+    //when the commit phase ends, Unix epoch in milliseconds
+    pub commit_ends_at: u64,
+    //when the reveal phase ends, Unix epoch in milliseconds
+    pub reveal_ends_at: u64,
+    //the bids committed so far, keyed by bidder
+    pub bids: HashMap<AccountId, SealedBid>,
+}
+
+//hash a bid and salt the same way bidders are expected to when committing
+pub(crate) fn hash_bid(bid: NearToken, salt: &str) -> Vec<u8> {
+    //the bid is encoded with a fixed width so that no other bid and salt can produce the same preimage
+    let mut preimage = bid.as_yoctonear().to_le_bytes().to_vec();
+    preimage.extend_from_slice(salt.as_bytes());
+    env::sha256(&preimage)
+}
+
+//find the winner of an auction. The highest revealed bid wins and pays the second-highest revealed bid,
+//or the reserve price if nobody else revealed. Ties are broken in favor of the lowest account ID.
+pub(crate) fn vickrey_outcome(
+    bids: &HashMap<AccountId, SealedBid>,
+    reserve_price: NearToken,
+) -> Option<(AccountId, NearToken)> {
+    //collect every bid that was revealed
+    let mut revealed: Vec<(&AccountId, NearToken)> = bids
+        .iter()
+        .filter_map(|(bidder_id, bid)| bid.revealed_bid.map(|amount| (bidder_id, amount)))
+        .collect();
+    //sort the bids from highest to lowest
+    revealed.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+
+    //the winner is the first bid. If there were no revealed bids, there is no winner
+    let (winner_id, _) = revealed.first()?;
+    //the price is the second-highest bid if there is one. Otherwise it's the reserve price
+    let price = revealed
+        .get(1)
+        .map(|(_, amount)| *amount)
+        .unwrap_or(reserve_price);
+
+    Some(((*winner_id).clone(), price))
+}
+
+#[near_bindgen]
+impl Contract {
+    //lists a nft as a sealed-bid auction on the market. The reserve price is the lowest bid that can win
+    #[payable]
+    pub fn list_nft_for_auction(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        approval_id: u64,
+        reserve_price: SalePriceInYoctoNear,
+        commit_ends_at: u64,
+        reveal_ends_at: u64,
+    ) {
+        let owner_id = env::predecessor_account_id();
+
+        //make sure the timeline makes sense
+        assert!(
+            commit_ends_at > env::block_timestamp_ms(),
+            "The commit phase must end in the future"
+        );
+        assert!(
+            reveal_ends_at > commit_ends_at,
+            "The reveal phase must end after the commit phase"
+        );
+
+        //verify the token with the nft contract and list it once the checks pass
+        self.internal_list_nft(
+            owner_id,
+            nft_contract_id,
+            token_id,
+            approval_id,
+            reserve_price,
+            Some(SealedBidAuction {
+                commit_ends_at,
+                reveal_ends_at,
+            }),
+        );
+    }
+
+    //commit a sealed bid to an auction. The attached deposit pays for storing a new bid and the rest is held in escrow,
+    //which must cover the bid that is revealed later.
+    //Committing again replaces the commitment and adds the attached deposit to what is already escrowed.
+    #[payable]
+    pub fn commit_bid(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        commitment: Base64VecU8,
+    ) {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+
+        //get the sale and the auction. If either doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let auction = self
+            .auctions
+            .get(&contract_and_token_id)
+            .expect("Sale is not a sealed-bid auction");
+
+        //make sure the bidder isn't the owner of the sale
+        let bidder_id = env::predecessor_account_id();
+        assert_ne!(sale.owner_id, bidder_id, "Cannot bid on your own sale.");
+
+        //bids can only be committed before the commit phase ends
+        assert!(
+            env::block_timestamp_ms() < auction.commit_ends_at,
+            "The commit phase has ended"
+        );
+        //the commitment must be a sha256 hash
+        assert_eq!(commitment.0.len(), 32, "Commitment must be a sha256 hash");
+
+        //get the bids for the auction and add the attached deposit to anything the bidder already escrowed
+        let mut bids = self
+            .auction_bids
+            .get(&contract_and_token_id)
+            .unwrap_or_default();
+        //bidders that already committed can always top up, but new bidders can't join a full auction
+        assert!(
+            bids.contains_key(&bidder_id) || bids.len() < MAX_BIDDERS_PER_AUCTION,
+            "Auction cannot have more than {} bidders",
+            MAX_BIDDERS_PER_AUCTION
+        );
+        let (deposit, storage_deposit) = match bids.get(&bidder_id) {
+            //the bidder already paid for the storage of their bid so everything attached is escrowed
+            Some(bid) => (
+                bid.deposit.saturating_add(env::attached_deposit()),
+                bid.storage_deposit,
+            ),
+            None => {
+                //measure the storage of the new bid. It's measured as revealed so it covers the revealed bid as well
+                let initial_storage_usage = env::storage_usage();
+                bids.insert(
+                    bidder_id.clone(),
+                    SealedBid {
+                        commitment: commitment.clone(),
+                        deposit: ZERO_NEAR,
+                        storage_deposit: ZERO_NEAR,
+                        revealed_bid: Some(ZERO_NEAR),
+                    },
+                );
+                self.auction_bids.insert(&contract_and_token_id, &bids);
+                let storage_deposit = env::storage_byte_cost().saturating_mul(
+                    env::storage_usage()
+                        .saturating_sub(initial_storage_usage)
+                        .into(),
+                );
+
+                //the storage is paid out of the attached deposit and the rest is escrowed
+                let deposit = env::attached_deposit()
+                    .checked_sub(storage_deposit)
+                    .unwrap_or_else(|| {
+                        env::panic_str(&format!(
+                            "Must attach at least {} to pay for the storage of the bid",
+                            storage_deposit
+                        ))
+                    });
+                (deposit, storage_deposit)
+            }
+        };
+
+        //the escrow must be able to cover the reserve price
+        assert!(
+            deposit.ge(&sale.sale_conditions),
+            "Escrowed deposit must be at least {}. Your deposit: {}",
+            sale.sale_conditions,
+            deposit
+        );
+
+        //insert the bid and the bids back into the map
+        bids.insert(
+            bidder_id,
+            SealedBid {
+                commitment,
+                deposit,
+                storage_deposit,
+                revealed_bid: None,
+            },
+        );
+        self.auction_bids.insert(&contract_and_token_id, &bids);
+    }
+
+    //reveal a committed bid during the reveal phase by opening the commitment with the bid and salt
+    pub fn reveal_bid(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        bid: NearToken,
+        salt: String,
+    ) {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+
+        //get the sale and the auction. If either doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let auction = self
+            .auctions
+            .get(&contract_and_token_id)
+            .expect("Sale is not a sealed-bid auction");
+
+        //bids can only be revealed between the end of the commit phase and the end of the reveal phase
+        let now = env::block_timestamp_ms();
+        assert!(
+            now >= auction.commit_ends_at,
+            "The reveal phase has not started"
+        );
+        assert!(now < auction.reveal_ends_at, "The reveal phase has ended");
+
+        //get the bidder's committed bid
+        let bidder_id = env::predecessor_account_id();
+        let mut bids = self
+            .auction_bids
+            .get(&contract_and_token_id)
+            .unwrap_or_default();
+        let sealed_bid = bids.get_mut(&bidder_id).expect("No bid to reveal");
+        assert!(
+            sealed_bid.revealed_bid.is_none(),
+            "Bid has already been revealed"
+        );
+
+        //make sure the bid and salt open the commitment
+        assert_eq!(
+            hash_bid(bid, &salt),
+            sealed_bid.commitment.0,
+            "Bid and salt do not match the commitment"
+        );
+        //make sure the bid is valid
+        assert!(
+            bid.le(&sealed_bid.deposit),
+            "Revealed bid of {} exceeds the escrowed deposit of {}",
+            bid,
+            sealed_bid.deposit
+        );
+        assert!(
+            bid.ge(&sale.sale_conditions),
+            "Revealed bid of {} is below the reserve price of {}",
+            bid,
+            sale.sale_conditions
+        );
+
+        //store the revealed bid
+        sealed_bid.revealed_bid = Some(bid);
+        self.auction_bids.insert(&contract_and_token_id, &bids);
+    }
+
+    //settle an auction once the reveal phase is over. Anyone can call this.
+    //The highest revealed bid wins at the second-highest price and every other deposit is refunded.
+    pub fn settle_auction(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+
+        //get the sale and the auction. If either doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let auction = self
+            .auctions
+            .get(&contract_and_token_id)
+            .expect("Sale is not a sealed-bid auction");
+
+        //the auction can only be settled once the reveal phase is over
+        assert!(
+            env::block_timestamp_ms() >= auction.reveal_ends_at,
+            "The reveal phase has not ended"
+        );
+
+        //remove the bids and find the winner
+        let bids = self
+            .auction_bids
+            .remove(&contract_and_token_id)
+            .unwrap_or_default();
+        let outcome = vickrey_outcome(&bids, sale.sale_conditions);
+
+        //refund every deposit and the storage of every bid. The winner only gets back what is left over after paying the price
+        for (bidder_id, bid) in bids.iter() {
+            let refund = match &outcome {
+                Some((winner_id, price)) if winner_id == bidder_id => {
+                    bid.deposit.saturating_sub(*price)
+                }
+                _ => bid.deposit,
+            }
+            .saturating_add(bid.storage_deposit);
+            if refund.gt(&ZERO_NEAR) {
+                Promise::new(bidder_id.clone()).transfer(refund);
+            }
+        }
+
+        if let Some((winner_id, price)) = outcome {
+            //process the purchase (which will remove the sale, transfer and get the payout from the nft contract, and then distribute royalties)
+            self.process_purchase(nft_contract_id, token_id, price, winner_id);
+        } else {
+            //nobody revealed a valid bid so we simply take the sale down
+            self.internal_remove_sale(nft_contract_id, token_id);
+        }
+    }
+}
+
+impl Contract {
+    //make sure that nobody has committed a bid to the sale if it's an auction
+    pub(crate) fn assert_no_auction_bids(&self, nft_contract_id: &AccountId, token_id: &TokenId) {
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        assert!(
+            self.auction_bids
+                .get(&contract_and_token_id)
+                .map_or(true, |bids| bids.is_empty()),
+            "Auction already has bids"
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use crate::*;
+use near_sdk::{log, NearSchema, PromiseResult};
+
+//the maximum number of sales that can be bought at once (this is limited by GAS)
+const MAX_BUY_MANY_ITEMS: usize = 6;
+//GAS for resolving the purchases. Every purchase needs enough GAS to parse its payout and pay the accounts
+const GAS_FOR_RESOLVE_BUY_MANY: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_BUY_MANY_PER_ITEM: Gas = Gas::from_tgas(15);
+
+//a sale that should be bought as part of buy_many
+#[derive(Serialize, Deserialize, NearSchema, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BuyManyItem {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID to buy
+    pub token_id: TokenId,
+    //the most the buyer is willing to pay for the token. If the sale is listed for more, it's skipped
+    pub max_price: NearToken,
+}
+
+//a purchase that was started by buy_many and is waiting for the nft contract
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingPurchase {
+    //index of the item that was passed into buy_many
+    pub index: usize,
+    //the price the token is being bought for
+    pub price: NearToken,
+    //the removed sale if the market held the token, so the seller can be paid or the token listed again
+    pub custody_sale: Option<Sale>,
+}
+
+//the result of buying a single item with buy_many
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BuyManyResult {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID that was bought
+    pub token_id: TokenId,
+    //the price the token was bought for. If this is None, the token wasn't bought and nothing was charged for it
+    pub price: Option<NearToken>,
+}
+
+#[near_bindgen]
+impl Contract {
+    //buy several sales at once with a single attached deposit. Every item is bought at its listed price as long as it's
+    //no more than the item's max price and there is enough deposit left. The purchases run in parallel and everything
+    //that wasn't spent is refunded in one transfer once they're all resolved.
+    #[payable]
+    pub fn buy_many(&mut self, items: Vec<BuyManyItem>) -> PromiseOrValue<Vec<BuyManyResult>> {
+        assert!(!items.is_empty(), "Must buy at least 1 item");
+        assert!(
+            items.len() <= MAX_BUY_MANY_ITEMS,
+            "Cannot buy more than {} items at once",
+            MAX_BUY_MANY_ITEMS
+        );
+
+        let buyer_id = env::predecessor_account_id();
+        let deposit = env::attached_deposit();
+
+        //start a purchase for every item that can be bought and keep track of how much of the deposit is left
+        let mut remaining = deposit;
+        let mut purchases = Vec::new();
+        let mut promise: Option<Promise> = None;
+        for (index, item) in items.iter().enumerate() {
+            let price = match self.internal_buy_many_price(item, &buyer_id, remaining) {
+                Some(price) => price,
+                None => continue,
+            };
+            remaining = remaining.saturating_sub(price);
+
+            //remove the sale and initiate the transfer. All the transfers are joined so they run in parallel
+            let (transfer_payout_promise, custody_sale) = self.internal_transfer_payout(
+                item.nft_contract_id.clone(),
+                item.token_id.clone(),
+                price,
+                buyer_id.clone(),
+            );
+            promise = Some(match promise {
+                Some(promise) => promise.and(transfer_payout_promise),
+                None => transfer_payout_promise,
+            });
+            purchases.push(PendingPurchase {
+                index,
+                price,
+                custody_sale,
+            });
+        }
+
+        //if nothing could be bought, we refund the whole deposit right away
+        let promise = match promise {
+            Some(promise) => promise,
+            None => {
+                Promise::new(buyer_id).transfer(deposit);
+                return PromiseOrValue::Value(buy_many_results(items, &[]));
+            }
+        };
+
+        //resolve all the purchases at once. This pays the sellers and refunds the buyer in a single transfer
+        let resolve_gas = GAS_FOR_RESOLVE_BUY_MANY
+            .saturating_add(GAS_FOR_RESOLVE_BUY_MANY_PER_ITEM.saturating_mul(purchases.len() as u64));
+        promise
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(resolve_gas)
+                    .resolve_buy_many(buyer_id, items, purchases, remaining),
+            )
+            .into()
+    }
+
+    /*
+        private method used to resolve the promises when calling nft_transfer_payout for every purchase started by buy_many.
+        Every payout is checked the same way as in resolve_purchase. Purchases that went through pay the accounts in the payout,
+        and the price of every purchase that failed is added to the refund along with the deposit that wasn't spent.
+    */
+    #[private]
+    pub fn resolve_buy_many(
+        &mut self,
+        buyer_id: AccountId,
+        items: Vec<BuyManyItem>,
+        purchases: Vec<PendingPurchase>,
+        unspent: NearToken,
+    ) -> Vec<BuyManyResult> {
+        let mut refund = unspent;
+        let mut bought = Vec::new();
+
+        //the promise results are in the same order as the purchases
+        for (result_index, purchase) in purchases.into_iter().enumerate() {
+            let payout = match env::promise_result(result_index as u64) {
+                PromiseResult::Successful(value) => parse_payout(&value, purchase.price),
+                _ => None,
+            };
+
+            if let Some(mut payout) = payout {
+                //the purchase went through so we pay the accounts
+                if let Some(sale) = &purchase.custody_sale {
+                    credit_market_share_to_seller(&mut payout, &sale.owner_id);
+                }
+                let item = &items[purchase.index];
+                let sale_context = SaleContext {
+                    nft_contract_id: item.nft_contract_id.clone(),
+                    buyer_id: buyer_id.clone(),
+                    price: purchase.price,
+                };
+                self.internal_distribute_payout(payout, &item.token_id, &sale_context);
+                bought.push((purchase.index, purchase.price));
+            } else {
+                //something went wrong so the buyer is refunded the price
+                log!("Purchase of item {} failed", purchase.index);
+                refund = refund.saturating_add(purchase.price);
+                //the market still holds the token so we put the sale back up
+                if let Some(sale) = purchase.custody_sale {
+                    self.internal_insert_sale(sale, None);
+                }
+            }
+        }
+
+        if refund.gt(&ZERO_NEAR) {
+            Promise::new(buyer_id).transfer(refund);
+        }
+
+        buy_many_results(items, &bought)
+    }
+}
+
+impl Contract {
+    //get the price an item can be bought for with buy_many. Returns None if the item should be skipped
+    pub(crate) fn internal_buy_many_price(
+        &self,
+        item: &BuyManyItem,
+        buyer_id: &AccountId,
+        remaining: NearToken,
+    ) -> Option<NearToken> {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (item.nft_contract_id.clone(), item.token_id.clone());
+
+        //the token has to be for sale. It may have been bought already, even by an earlier item in the same cart
+        let sale = self.sales.get(&contract_and_token_id)?;
+
+        //sealed-bid auctions and reserved sales can't be bought outright, and buyers can't buy their own sales
+        let is_reserved = self
+            .layaways
+            .get(&contract_and_token_id)
+            .map_or(false, |layaway| layaway.reservation.is_some());
+        if self.auctions.get(&contract_and_token_id).is_some() || is_reserved || &sale.owner_id == buyer_id {
+            return None;
+        }
+
+        //the price has to be within the buyer's limit and what's left of the deposit
+        let price = sale.sale_conditions;
+        if price.gt(&item.max_price) || price.gt(&remaining) {
+            return None;
+        }
+        Some(price)
+    }
+}
+
+//build the result for every item passed into buy_many from the items that were bought and their prices
+fn buy_many_results(items: Vec<BuyManyItem>, bought: &[(usize, NearToken)]) -> Vec<BuyManyResult> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(index, item)| BuyManyResult {
+            nft_contract_id: item.nft_contract_id,
+            token_id: item.token_id,
+            price: bought
+                .iter()
+                .find(|(bought_index, _)| *bought_index == index)
+                .map(|(_, price)| *price),
+        })
+        .collect()
+}
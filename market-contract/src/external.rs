@@ -20,6 +20,14 @@ trait ExtContract {
         //the maximum amount of accounts the market can payout at once (this is limited by GAS)
         max_len_payout: u32,
     );
+    //This will transfer the token to the receiver. Used to move tokens in and out of the market's custody
+    fn nft_transfer(
+        &mut self,
+        receiver_id: AccountId,
+        token_id: TokenId,
+        approval_id: Option<u64>,
+        memo: Option<String>,
+    );
     fn nft_token(&self, token_id: TokenId);
     fn nft_is_approved(&self, token_id: TokenId, approved_account_id: AccountId, approval_id: u64);
 }
@@ -1,4 +1,5 @@
 use crate::*;
+use near_sdk::serde_json::json;
 
 //used to generate a unique prefix in our storage collections (this is to avoid data collisions)
 pub(crate) fn hash_account_id(account_id: &AccountId) -> CryptoHash {
@@ -13,6 +14,55 @@ pub(crate) fn storage_per_sale() -> NearToken {
   env::storage_byte_cost().saturating_mul(1000)
 }
 
+//parse the payout object returned from nft_transfer_payout and check to see if it's authentic for the given price.
+//returns None if there's a problem with the payout
+pub(crate) fn parse_payout(value: &[u8], price: NearToken) -> Option<HashMap<AccountId, NearToken>> {
+    near_sdk::serde_json::from_slice::<Payout>(value)
+        //converts the result to an optional value
+        .ok()
+        //returns None if the none. Otherwise executes the following logic
+        .and_then(|payout_object| {
+            //we'll check if length of the payout object is > 10 or it's empty. In either case, we return None
+            if payout_object.payout.len() > 10 || payout_object.payout.is_empty() {
+                env::log_str("Cannot have more than 10 royalties");
+                None
+
+            //if the payout object is the correct length, we move forward
+            } else {
+                //we'll keep track of how much the nft contract wants us to payout. Starting at the full price payed by the buyer
+                let mut remainder = price;
+
+                //loop through the payout and subtract the values from the remainder.
+                for &value in payout_object.payout.values() {
+                    //checked sub checks for overflow or any errors and returns None if there are problems
+                    remainder = remainder.checked_sub(value)?;
+                }
+                //Check to see if the NFT contract sent back a faulty payout that requires us to pay more or too little.
+                //The remainder will be 0 if the payout summed to the total price. The remainder will be 1 if the royalties
+                //we something like 3333 + 3333 + 3333.
+                if remainder.eq(&ZERO_NEAR) || remainder.eq(&NearToken::from_yoctonear(1)) {
+                    //set the payout_option to be the payout because nothing went wrong
+                    Some(payout_object.payout)
+                } else {
+                    //if the remainder was anything but 1 or 0, we return None
+                    None
+                }
+            }
+        })
+}
+
+//when the market itself owned the token that was sold, the owner's share of the payout is keyed by the market's account.
+//we credit that share to the seller instead
+pub(crate) fn credit_market_share_to_seller(
+    payout: &mut HashMap<AccountId, NearToken>,
+    seller_id: &AccountId,
+) {
+    if let Some(market_share) = payout.remove(&env::current_account_id()) {
+        let seller_share = payout.entry(seller_id.clone()).or_insert(ZERO_NEAR);
+        *seller_share = seller_share.saturating_add(market_share);
+    }
+}
+
 impl Contract {
     //internal method for removing a sale from the market. This returns the previously removed sale object
     pub(crate) fn internal_remove_sale(
@@ -20,10 +70,18 @@ impl Contract {
         nft_contract_id: AccountId,
         token_id: TokenId,
     ) -> Sale {
-        //get the unique sale ID (contract + DELIMITER + token ID)
-        let contract_and_token_id = format!("{}{}{}", &nft_contract_id, DELIMETER, token_id);
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
         //get the sale object by removing the unique sale ID. If there was no sale, panic
         let sale = self.sales.remove(&contract_and_token_id).expect("No sale");
+        //if the sale was a sealed-bid auction, we remove its timeline as well
+        self.auctions.remove(&contract_and_token_id);
+
+        //if the sale had layaway terms, we remove them as well. They're charged to the owner as they change,
+        //so the storage they free up is measured on its own
+        let initial_storage_usage = env::storage_usage();
+        self.layaways.remove(&contract_and_token_id);
+        let released_layaway_storage = initial_storage_usage.saturating_sub(env::storage_usage());
 
         //get the set of sales for the sale's owner. If there's no sale, panic. 
         let mut by_owner_id = self.by_owner_id.get(&sale.owner_id).expect("No sale by_owner_id");
@@ -56,7 +114,258 @@ impl Contract {
                 .insert(&nft_contract_id, &by_nft_contract_id);
         }
 
+        //release exactly the storage the owner was charged for the sale so they can withdraw it
+        let sale_storage = self.sale_storage.remove(&contract_and_token_id).unwrap_or(0);
+        self.internal_release_storage(
+            &sale.owner_id,
+            sale_storage.saturating_add(released_layaway_storage),
+        );
+
         //return the sale object
         sale
     }
+
+    //internal method for listing a token. This verifies the token with the nft contract and then lists it in
+    //the process_listing callback. If an auction is passed in, the sale is listed as a sealed-bid auction
+    pub(crate) fn internal_list_nft(
+        &mut self,
+        owner_id: AccountId,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        approval_id: u64,
+        sale_conditions: SalePriceInYoctoNear,
+        auction: Option<SealedBidAuction>,
+    ) {
+        //the exact storage for the sale is charged once it's listed in process_listing. We fail early here
+        //if the owner has no storage left at all so they don't waste GAS on the cross contract calls
+        assert!(
+            self.storage_available_of(owner_id.clone()).gt(&ZERO_NEAR),
+            "Insufficient storage paid: {}, with {} bytes used",
+            self.storage_balance_of(owner_id.clone()),
+            self.storage_used_of(owner_id.clone()).0
+        );
+        //fail early if the token is already listed
+        self.assert_not_listed(&nft_contract_id, &token_id);
+
+        let nft_token_promise = Promise::new(nft_contract_id.clone()).function_call(
+            "nft_token".to_owned(),
+            json!({ "token_id": token_id }).to_string().into_bytes(),
+            ZERO_NEAR,
+            Gas::from_gas(10u64.pow(13)),
+        );
+        let nft_is_approved_promise = Promise::new(nft_contract_id.clone()).function_call(
+          "nft_is_approved".to_owned(),
+          json!({ "token_id": token_id, "approved_account_id": env::current_account_id(), "approval_id": approval_id }).to_string().into_bytes(),
+          ZERO_NEAR,
+          Gas::from_gas(10u64.pow(13))
+        );
+        nft_token_promise.and(nft_is_approved_promise).then(
+            Self::ext(env::current_account_id()).process_listing(
+                owner_id.clone(),
+                nft_contract_id,
+                token_id,
+                approval_id,
+                sale_conditions,
+                auction,
+            ),
+        );
+    }
+
+    //make sure the token isn't listed yet. A listing can only be replaced by removing it first, so the terms of
+    //an auction with bids can't be changed by listing the token again
+    pub(crate) fn assert_not_listed(&self, nft_contract_id: &AccountId, token_id: &TokenId) {
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        assert!(
+            self.sales.get(&contract_and_token_id).is_none(),
+            "Token is already listed"
+        );
+    }
+
+    //make sure that the storage the owner has paid for covers all the storage their sales are using
+    pub(crate) fn assert_storage_covered(&self, owner_id: &AccountId) {
+        //get the total storage paid by the owner
+        let owner_paid_storage = self.storage_deposits.get(owner_id).unwrap_or(ZERO_NEAR);
+        //get the cost of the bytes being used by the owner's sales
+        let used_bytes = self.storage_used.get(owner_id).unwrap_or(0);
+        let signer_storage_required = env::storage_byte_cost().saturating_mul(used_bytes.into());
+
+        //make sure that the total paid is >= the required storage
+        assert!(
+            owner_paid_storage.ge(&signer_storage_required),
+            "Insufficient storage paid: {}, for {} bytes at {} rate of per byte",
+            owner_paid_storage,
+            used_bytes,
+            env::storage_byte_cost()
+        );
+    }
+
+    //add the bytes used by a new sale to the storage used by its owner
+    pub(crate) fn internal_charge_storage(&mut self, owner_id: &AccountId, bytes: u64) {
+        let used_bytes = self.storage_used.get(owner_id).unwrap_or(0);
+        self.storage_used
+            .insert(owner_id, &used_bytes.saturating_add(bytes));
+    }
+
+    //remove the bytes freed by a sale from the storage used by its owner
+    pub(crate) fn internal_release_storage(&mut self, owner_id: &AccountId, bytes: u64) {
+        let used_bytes = self.storage_used.get(owner_id).unwrap_or(0).saturating_sub(bytes);
+        //if the owner isn't using any storage anymore, we remove them from the map
+        if used_bytes == 0 {
+            self.storage_used.remove(owner_id);
+        } else {
+            self.storage_used.insert(owner_id, &used_bytes);
+        }
+    }
+
+    //internal method for inserting a sale into the market and populating the collections necessary for the view calls.
+    //the storage used by the sale is charged to its owner. If an auction is passed in, the sale is a sealed-bid auction
+    pub(crate) fn internal_insert_sale(&mut self, sale: Sale, auction: Option<SealedBidAuction>) {
+        let owner_id = sale.owner_id.clone();
+        let nft_contract_id: AccountId = sale.nft_contract_id.parse().unwrap();
+        let token_id = sale.token_id.clone();
+        //create the unique sale ID which is the (contract, token ID) pair
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+
+        //get the token IDs for the given nft contract ID. If there are none, we create a new empty set. The set is
+        //shared by every sale on the nft contract so it's created before measuring and nobody is charged for it
+        let mut by_nft_contract_id = self
+            .by_nft_contract_id
+            .get(&nft_contract_id)
+            .unwrap_or_else(|| {
+                let by_nft_contract_id = UnorderedSet::new(StorageKey::ByNFTContractIdInner {
+                    //we get a new unique prefix for the collection by hashing the owner
+                    account_id_hash: hash_account_id(&nft_contract_id),
+                });
+                self.by_nft_contract_id
+                    .insert(&nft_contract_id, &by_nft_contract_id);
+                by_nft_contract_id
+            });
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //keep track of the bytes charged for the sale so exactly that much is released when it's removed.
+        //the value has a fixed size so it's stored up front to be part of the measurement
+        self.sale_storage.insert(&contract_and_token_id, &0);
+
+        //insert the key value pair into the sales map. Key is the unique ID. value is the sale object
+        self.sales.insert(&contract_and_token_id, &sale);
+
+        //get the sales by owner ID for the given owner. If there are none, we create a new empty set
+        let mut by_owner_id = self.by_owner_id.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::ByOwnerIdInner {
+                //we get a new unique prefix for the collection by hashing the owner
+                account_id_hash: hash_account_id(&owner_id),
+            })
+        });
+
+        //insert the unique sale ID into the set
+        by_owner_id.insert(&contract_and_token_id);
+        //insert that set back into the collection for the owner
+        self.by_owner_id.insert(&owner_id, &by_owner_id);
+
+        //insert the token ID into the set
+        by_nft_contract_id.insert(&token_id);
+        //insert the set back into the collection for the given nft contract ID
+        self.by_nft_contract_id
+            .insert(&nft_contract_id, &by_nft_contract_id);
+
+        //if the sale is a sealed-bid auction, we store its timeline so that bids can be committed and revealed
+        if let Some(auction) = auction {
+            self.auctions.insert(&contract_and_token_id, &auction);
+        }
+
+        //charge the owner for the storage the sale actually uses
+        let required_storage = env::storage_usage().saturating_sub(initial_storage_usage);
+        self.sale_storage
+            .insert(&contract_and_token_id, &required_storage);
+        self.internal_charge_storage(&owner_id, required_storage);
+    }
+
+    //internal method for listing a token that was just transferred to the market. The market holds the token
+    //until it's bought or the sale is removed, so there's nothing to verify with the nft contract
+    pub(crate) fn internal_list_in_custody(
+        &mut self,
+        owner_id: AccountId,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        sale_conditions: SalePriceInYoctoNear,
+    ) {
+        //if the token is already listed, the nft contract gives the token back to the owner
+        self.assert_not_listed(&nft_contract_id, &token_id);
+
+        self.internal_insert_sale(
+            Sale {
+                owner_id: owner_id.clone(),                   //owner of the sale / token
+                approval_id: 0, //the market owns the token so no approval is needed to transfer it
+                nft_contract_id: nft_contract_id.to_string(), //NFT contract the token was minted on
+                token_id,                                     //the actual token ID
+                sale_conditions,                              //the sale conditions
+                in_custody: true,
+            },
+            None,
+        );
+
+        //the held token is charged for storage like any other listing.
+        //if this panics, the nft contract gives the token back to the owner
+        self.assert_storage_covered(&owner_id);
+    }
+
+    //internal method for giving a token held in custody back to the owner of its sale. The sale must already be removed
+    pub(crate) fn internal_return_from_custody(&mut self, sale: Sale) -> Promise {
+        let nft_contract_id: AccountId = sale.nft_contract_id.parse().unwrap();
+        ext_contract::ext(nft_contract_id)
+            .with_attached_deposit(ONE_YOCTONEAR)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer(
+                sale.owner_id.clone(),
+                sale.token_id.clone(),
+                None,
+                Some("returned from market custody".to_string()),
+            )
+            //if the transfer fails, the sale is put back up so the owner can try again
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_RETURN_FROM_CUSTODY)
+                    .resolve_return_from_custody(sale),
+            )
+    }
+
+    //internal method for removing a sale and initiating a cross contract call to the nft contract. This will transfer
+    //the token to the buyer and return a payout object used for the market to distribute funds to the appropriate accounts.
+    //if the market holds the token, the removed sale is returned alongside the promise
+    pub(crate) fn internal_transfer_payout(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        price: NearToken,
+        buyer_id: AccountId,
+    ) -> (Promise, Option<Sale>) {
+        //get the sale object by removing the sale
+        let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
+        let custody_sale = if sale.in_custody {
+            Some(sale.clone())
+        } else {
+            None
+        };
+
+        let promise = ext_contract::ext(nft_contract_id)
+            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
+            .with_attached_deposit(ONE_YOCTONEAR)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer_payout(
+                buyer_id,                         //purchaser (person to transfer the NFT to)
+                token_id,                         //token ID to transfer
+                sale.approval_id, //market contract's approval ID in order to transfer the token on behalf of the owner
+                "payout from market".to_string(), //memo (to include some context)
+                /*
+                    the price that the token was purchased for. This will be used in conjunction with the royalty percentages
+                    for the token in order to determine how much money should go to which account.
+                */
+                price,
+                10, //the maximum amount of accounts the market can payout at once (this is limited by GAS)
+            );
+
+        (promise, custody_sale)
+    }
 }
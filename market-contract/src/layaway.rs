@@ -0,0 +1,346 @@
+use crate::*;
+use near_sdk::NearSchema;
+
+//the largest share of what a buyer paid that a seller can keep when a reservation defaults (100%)
+const MAX_FORFEIT_BPS: u32 = 10_000;
+//the longest a buyer can take to pay for a token, in milliseconds (1 year)
+const MAX_LAYAWAY_DURATION_MS: u64 = 365 * 86_400_000;
+
+//the terms a seller offers for buying a listed token in installments
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct LayawayTerms {
+    //the deposit in yoctoNEAR that a buyer has to pay to reserve the token
+    pub deposit: NearToken,
+    //how many installments the rest of the price is split into
+    pub installments: u32,
+    //how long the buyer has to pay each installment, in milliseconds
+    pub installment_period_ms: u64,
+    //the share of what the buyer paid that the seller keeps if the buyer defaults, in basis points
+    pub forfeit_bps: u32,
+}
+
+//a reservation of a token by a buyer who is paying for it in installments
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Reservation {
+    //account that reserved the token
+    pub buyer_id: AccountId,
+    //how much the buyer has paid so far, including the deposit
+    pub paid: NearToken,
+    //when the token was reserved, Unix epoch in milliseconds
+    pub reserved_at: u64,
+}
+
+//the layaway terms for a sale along with the current reservation, if there is one
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Layaway {
+    pub terms: LayawayTerms,
+    pub reservation: Option<Reservation>,
+}
+
+//The Json layaway is what will be returned from view calls.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonLayaway {
+    //the sale the layaway is for
+    pub sale: Sale,
+    //the terms offered by the seller
+    pub terms: LayawayTerms,
+    //the current reservation, if there is one
+    pub reservation: Option<Reservation>,
+    //how much the buyer has to have paid by now to keep the reservation
+    pub amount_due: Option<NearToken>,
+    //when the last installment is due, Unix epoch in milliseconds
+    pub deadline: Option<u64>,
+}
+
+//the share of what a buyer paid that the seller keeps. The whole and the remainder of the payment are split separately
+//so that the multiplication can't overflow
+pub(crate) fn forfeit_share(paid: NearToken, forfeit_bps: u32) -> NearToken {
+    let paid = paid.as_yoctonear();
+    let max_bps = u128::from(MAX_FORFEIT_BPS);
+    let forfeit_bps = u128::from(forfeit_bps);
+    NearToken::from_yoctonear(paid / max_bps * forfeit_bps + paid % max_bps * forfeit_bps / max_bps)
+}
+
+impl LayawayTerms {
+    //how long the buyer has to pay every installment, in milliseconds. This is None if it doesn't fit in a u64
+    pub(crate) fn duration_ms(&self) -> Option<u64> {
+        self.installment_period_ms
+            .checked_mul(u64::from(self.installments))
+    }
+}
+
+impl Layaway {
+    //when the last installment is due for the current reservation
+    pub(crate) fn deadline(&self) -> Option<u64> {
+        let reservation = self.reservation.as_ref()?;
+        self.terms
+            .duration_ms()
+            .and_then(|duration_ms| reservation.reserved_at.checked_add(duration_ms))
+    }
+
+    //how much the buyer has to have paid at the given time to keep the reservation. The deposit is due up front
+    //and every installment period that has ended adds an equal share of the rest of the price
+    pub(crate) fn amount_due(&self, price: NearToken, now: u64) -> Option<NearToken> {
+        let reservation = self.reservation.as_ref()?;
+        let periods_ended = (now.saturating_sub(reservation.reserved_at) / self.terms.installment_period_ms)
+            .min(u64::from(self.terms.installments));
+        let remainder = price.saturating_sub(self.terms.deposit).as_yoctonear();
+        let installments_due = remainder.checked_mul(u128::from(periods_ended))? / u128::from(self.terms.installments);
+        Some(
+            self.terms
+                .deposit
+                .saturating_add(NearToken::from_yoctonear(installments_due)),
+        )
+    }
+
+    //whether the buyer has missed an installment. An installment can still be paid at the moment it's due,
+    //so only the installments that were due before now count
+    pub(crate) fn is_defaulted(&self, price: NearToken, now: u64) -> bool {
+        match (&self.reservation, self.amount_due(price, now.saturating_sub(1))) {
+            (Some(reservation), Some(amount_due)) => reservation.paid.lt(&amount_due),
+            _ => false,
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //offer layaway terms for a listed token so that buyers can reserve it and pay in installments
+    #[payable]
+    pub fn set_layaway_terms(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        terms: LayawayTerms,
+    ) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id, token_id);
+
+        //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        assert_eq!(
+            env::predecessor_account_id(),
+            sale.owner_id,
+            "Must be sale owner"
+        );
+        //sealed-bid auctions are settled through bids so they can't be bought in installments
+        assert!(
+            self.auctions.get(&contract_and_token_id).is_none(),
+            "Sale is a sealed-bid auction"
+        );
+
+        //the terms can't change while someone has the token reserved
+        if let Some(layaway) = self.layaways.get(&contract_and_token_id) {
+            assert!(layaway.reservation.is_none(), "Sale is reserved");
+        }
+
+        //make sure the terms are valid
+        assert!(
+            terms.deposit.gt(&ZERO_NEAR) && terms.deposit.lt(&sale.sale_conditions),
+            "Deposit must be greater than 0 and less than the price"
+        );
+        assert!(terms.installments > 0, "There must be at least 1 installment");
+        assert!(
+            terms.installment_period_ms > 0,
+            "Installment period must be greater than 0"
+        );
+        assert!(
+            terms
+                .duration_ms()
+                .map_or(false, |duration_ms| duration_ms <= MAX_LAYAWAY_DURATION_MS),
+            "Installments cannot take longer than {} ms to pay",
+            MAX_LAYAWAY_DURATION_MS
+        );
+        assert!(
+            terms.forfeit_bps <= MAX_FORFEIT_BPS,
+            "Forfeit share cannot be more than {} basis points",
+            MAX_FORFEIT_BPS
+        );
+
+        //insert the terms and make sure the owner has paid for the storage they use
+        self.internal_write_layaway(
+            &contract_and_token_id,
+            &sale.owner_id,
+            &Layaway {
+                terms,
+                reservation: None,
+            },
+        );
+        self.assert_storage_covered(&sale.owner_id);
+    }
+
+    //reserve a token that has layaway terms by paying the deposit
+    #[payable]
+    pub fn reserve(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id, token_id);
+
+        //get the sale and its layaway terms. If either doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let mut layaway = self
+            .layaways
+            .get(&contract_and_token_id)
+            .expect("Sale has no layaway terms");
+        assert!(layaway.reservation.is_none(), "Sale is reserved");
+
+        //make sure the buyer isn't the owner of the sale
+        let buyer_id = env::predecessor_account_id();
+        assert_ne!(sale.owner_id, buyer_id, "Cannot reserve your own sale.");
+
+        //make sure the deposit is at least the one in the terms. Anything above it counts towards the next installments
+        let deposit = env::attached_deposit();
+        assert!(
+            deposit.ge(&layaway.terms.deposit),
+            "Attached deposit must be greater than or equal to the layaway deposit: {}. Your deposit: {}",
+            layaway.terms.deposit,
+            deposit
+        );
+
+        layaway.reservation = Some(Reservation {
+            buyer_id,
+            paid: ZERO_NEAR,
+            reserved_at: env::block_timestamp_ms(),
+        });
+        self.internal_pay_layaway(contract_and_token_id, sale, layaway, deposit);
+    }
+
+    //pay an installment towards a reserved token. Once the full price has been paid, the token is bought
+    #[payable]
+    pub fn pay_installment(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id, token_id);
+
+        //get the sale and its reservation. If either doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let layaway = self
+            .layaways
+            .get(&contract_and_token_id)
+            .expect("Sale has no layaway terms");
+        let reservation = layaway.reservation.as_ref().expect("Sale is not reserved");
+
+        //make sure the caller is the buyer and the reservation is still in good standing
+        assert_eq!(
+            env::predecessor_account_id(),
+            reservation.buyer_id,
+            "Must be the buyer of the reservation"
+        );
+        assert!(
+            !layaway.is_defaulted(sale.sale_conditions, env::block_timestamp_ms()),
+            "An installment was missed"
+        );
+
+        let deposit = env::attached_deposit();
+        assert!(
+            !deposit.is_zero(),
+            "Attached deposit must be greater than 0"
+        );
+        self.internal_pay_layaway(contract_and_token_id, sale, layaway, deposit);
+    }
+
+    //end a reservation where the buyer missed an installment. Anyone can call this.
+    //The seller keeps the forfeit share of what was paid, the buyer is refunded the rest and the sale reopens.
+    pub fn release_defaulted_layaway(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id, token_id);
+
+        //get the sale and its reservation. If either doesn't exist, panic.
+        let sale = self.sales.get(&contract_and_token_id).expect("No sale");
+        let mut layaway = self
+            .layaways
+            .get(&contract_and_token_id)
+            .expect("Sale has no layaway terms");
+        assert!(
+            layaway.is_defaulted(sale.sale_conditions, env::block_timestamp_ms()),
+            "Reservation is in good standing"
+        );
+
+        //split what the buyer paid between the seller and the buyer
+        let reservation = layaway.reservation.take().unwrap();
+        let forfeit = forfeit_share(reservation.paid, layaway.terms.forfeit_bps);
+        let refund = reservation.paid.saturating_sub(forfeit);
+        if forfeit.gt(&ZERO_NEAR) {
+            Promise::new(sale.owner_id.clone()).transfer(forfeit);
+        }
+        if refund.gt(&ZERO_NEAR) {
+            Promise::new(reservation.buyer_id).transfer(refund);
+        }
+
+        //the terms stay up so the token can be reserved again
+        self.internal_write_layaway(&contract_and_token_id, &sale.owner_id, &layaway);
+    }
+}
+
+impl Contract {
+    //add a payment to a reservation. If the full price has been paid, the token is bought
+    pub(crate) fn internal_pay_layaway(
+        &mut self,
+        contract_and_token_id: ContractAndTokenId,
+        sale: Sale,
+        mut layaway: Layaway,
+        deposit: NearToken,
+    ) {
+        let reservation = layaway.reservation.as_mut().unwrap();
+        reservation.paid = reservation.paid.saturating_add(deposit);
+
+        //if the buyer hasn't paid the full price yet, we store the payment and wait for the next installment
+        let price = sale.sale_conditions;
+        if reservation.paid.lt(&price) {
+            //the reservation is charged to the seller. If they haven't paid for its storage, the payment is refunded
+            self.internal_write_layaway(&contract_and_token_id, &sale.owner_id, &layaway);
+            self.assert_storage_covered(&sale.owner_id);
+            return;
+        }
+
+        //the buyer gets back anything they paid above the price
+        let buyer_id = reservation.buyer_id.clone();
+        let excess = reservation.paid.saturating_sub(price);
+        if excess.gt(&ZERO_NEAR) {
+            Promise::new(buyer_id.clone()).transfer(excess);
+        }
+
+        //process the purchase (which will remove the sale and layaway, transfer and get the payout from the nft contract, and then distribute royalties)
+        let (nft_contract_id, token_id) = contract_and_token_id;
+        self.process_purchase(nft_contract_id, token_id, price, buyer_id);
+    }
+
+    //insert a layaway. The change in storage is charged to or released from the seller
+    pub(crate) fn internal_write_layaway(
+        &mut self,
+        contract_and_token_id: &ContractAndTokenId,
+        owner_id: &AccountId,
+        layaway: &Layaway,
+    ) {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        self.layaways.insert(contract_and_token_id, layaway);
+
+        let storage_usage = env::storage_usage();
+        if storage_usage > initial_storage_usage {
+            self.internal_charge_storage(owner_id, storage_usage - initial_storage_usage);
+        } else {
+            self.internal_release_storage(owner_id, initial_storage_usage - storage_usage);
+        }
+    }
+
+    //make sure that nobody has reserved the sale
+    pub(crate) fn assert_not_reserved(&self, nft_contract_id: &AccountId, token_id: &TokenId) {
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+        assert!(
+            self.layaways
+                .get(&contract_and_token_id)
+                .map_or(true, |layaway| layaway.reservation.is_none()),
+            "Sale is reserved"
+        );
+    }
+}
@@ -1,39 +1,57 @@
 use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LookupMap, LookupSet, UnorderedMap, UnorderedSet};
 use near_sdk::json_types::{U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 use near_sdk::{
     assert_one_yocto, env, ext_contract, near_bindgen, AccountId, BorshStorageKey, CryptoHash, Gas,
-    NearToken, PanicOnDefault, Promise,
+    NearToken, PanicOnDefault, Promise, PromiseOrValue,
 };
 use std::collections::HashMap;
 
+use crate::auction::*;
+use crate::buy_many::*;
 use crate::external::*;
 use crate::internal::*;
+use crate::layaway::*;
+use crate::rental::*;
+use crate::royalty_receiver::*;
 use crate::sale::*;
+use crate::standing_offer::*;
+use crate::swap::*;
 
+mod auction;
+mod buy_many;
 mod external;
 mod internal;
+mod layaway;
+mod migrate;
 mod nft_callbacks;
+mod rental;
+mod rental_views;
+mod royalty_receiver;
 mod sale;
 mod sale_views;
+mod standing_offer;
+mod standing_offer_views;
+mod swap;
+mod swap_views;
 
 //GAS constants to attach to calls
 const GAS_FOR_RESOLVE_PURCHASE: Gas = Gas::from_tgas(115);
 const GAS_FOR_NFT_TRANSFER: Gas = Gas::from_tgas(15);
+const GAS_FOR_RESOLVE_RETURN_FROM_CUSTODY: Gas = Gas::from_tgas(10);
 
 //Basic NEAR amounts as constants
 const ZERO_NEAR: NearToken = NearToken::from_yoctonear(0);
 const ONE_YOCTONEAR: NearToken = NearToken::from_yoctonear(1);
 
-//every sale will have a unique ID which is `CONTRACT + DELIMITER + TOKEN_ID`
-static DELIMETER: &str = ".";
-
 //Creating custom types to use within the contract. This makes things more readable.
 pub type SalePriceInYoctoNear = NearToken;
 pub type TokenId = String;
 pub type FungibleTokenId = AccountId;
-pub type ContractAndTokenId = String;
+//every sale will have a unique ID which is the `(CONTRACT, TOKEN_ID)` pair. Both parts are length-prefixed
+//when serialized so no two pairs can share a key, even though account IDs and token IDs can contain dots
+pub type ContractAndTokenId = (AccountId, TokenId);
 
 //defines the payout type we'll be parsing from the NFT contract as a part of the royalty standard.
 #[derive(Serialize, Deserialize)]
@@ -53,7 +71,7 @@ pub struct Contract {
     /*
         to keep track of the sales, we map the ContractAndTokenId to a Sale.
         the ContractAndTokenId is the unique identifier for every sale. It is made
-        up of the `contract ID + DELIMITER + token ID`
+        up of the `(contract ID, token ID)` pair
     */
     pub sales: UnorderedMap<ContractAndTokenId, Sale>,
 
@@ -65,6 +83,51 @@ pub struct Contract {
 
     //keep track of the storage that accounts have payed
     pub storage_deposits: LookupMap<AccountId, NearToken>,
+
+    //keep track of the bytes of storage that every account's sales are using
+    pub storage_used: LookupMap<AccountId, u64>,
+
+    //keep track of the bytes of storage that were charged for every sale
+    pub sale_storage: LookupMap<ContractAndTokenId, u64>,
+
+    //keep track of the timeline for every sale that is listed as a sealed-bid auction
+    pub auctions: LookupMap<ContractAndTokenId, SealedBidAuction>,
+
+    //keep track of the sealed bids committed to each auction
+    pub auction_bids: LookupMap<ContractAndTokenId, HashMap<AccountId, SealedBid>>,
+
+    //keep track of the open swap proposals
+    pub swaps: UnorderedMap<SwapId, SwapProposal>,
+
+    //keep track of the open swap proposals for every account (both as proposer and counterparty)
+    pub swaps_by_account: LookupMap<AccountId, UnorderedSet<SwapId>>,
+
+    //the ID that will be given to the next swap proposal
+    pub next_swap_id: SwapId,
+
+    //keep track of the tokens the market couldn't hand out after a swap and who can claim them
+    pub swap_claims: LookupMap<ContractAndTokenId, AccountId>,
+
+    //keep track of the standing offers to buy tokens
+    pub standing_offers: UnorderedMap<StandingOfferId, StandingOffer>,
+
+    //keep track of all the standing offer IDs for a given contract
+    pub standing_offers_by_nft_contract_id: LookupMap<AccountId, UnorderedSet<StandingOfferId>>,
+
+    //the ID that will be given to the next standing offer
+    pub next_standing_offer_id: StandingOfferId,
+
+    //keep track of the tokens held by the market to be rented out
+    pub rentals: UnorderedMap<ContractAndTokenId, Rental>,
+
+    //keep track of the layaway terms and reservations for sales that can be bought in installments
+    pub layaways: LookupMap<ContractAndTokenId, Layaway>,
+
+    //keep track of the royalty receiver contracts that are notified when they're paid out
+    pub royalty_receivers: LookupSet<AccountId>,
+
+    //keep track of the royalties that couldn't be paid to royalty receiver contracts and can be claimed
+    pub unpaid_royalties: LookupMap<AccountId, NearToken>,
 }
 
 /// Helper structure to for keys of the persistent collections.
@@ -80,6 +143,21 @@ pub enum StorageKey {
     ByNFTTokenTypeInner { token_type_hash: CryptoHash },
     FTTokenIds,
     StorageDeposits,
+    Auctions,
+    AuctionBids,
+    Swaps,
+    SwapsByAccount,
+    SwapsByAccountInner { account_id_hash: CryptoHash },
+    SwapClaims,
+    StandingOffers,
+    StandingOffersByNFTContractId,
+    StandingOffersByNFTContractIdInner { account_id_hash: CryptoHash },
+    StorageUsed,
+    SaleStorage,
+    Rentals,
+    Layaways,
+    RoyaltyReceivers,
+    UnpaidRoyalties,
 }
 
 #[near_bindgen]
@@ -100,6 +178,23 @@ impl Contract {
             by_owner_id: LookupMap::new(StorageKey::ByOwnerId),
             by_nft_contract_id: LookupMap::new(StorageKey::ByNFTContractId),
             storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+            storage_used: LookupMap::new(StorageKey::StorageUsed),
+            sale_storage: LookupMap::new(StorageKey::SaleStorage),
+            auctions: LookupMap::new(StorageKey::Auctions),
+            auction_bids: LookupMap::new(StorageKey::AuctionBids),
+            swaps: UnorderedMap::new(StorageKey::Swaps),
+            swaps_by_account: LookupMap::new(StorageKey::SwapsByAccount),
+            next_swap_id: 0,
+            swap_claims: LookupMap::new(StorageKey::SwapClaims),
+            standing_offers: UnorderedMap::new(StorageKey::StandingOffers),
+            standing_offers_by_nft_contract_id: LookupMap::new(
+                StorageKey::StandingOffersByNFTContractId,
+            ),
+            next_standing_offer_id: 0,
+            rentals: UnorderedMap::new(StorageKey::Rentals),
+            layaways: LookupMap::new(StorageKey::Layaways),
+            royalty_receivers: LookupSet::new(StorageKey::RoyaltyReceivers),
+            unpaid_royalties: LookupMap::new(StorageKey::UnpaidRoyalties),
         };
 
         //return the Contract object
@@ -152,12 +247,8 @@ impl Contract {
         //get the amount that the user has by removing them from the map. If they're not in the map, default to 0
         let mut amount = self.storage_deposits.remove(&owner_id).unwrap_or(ZERO_NEAR);
 
-        //how many sales is that user taking up currently. This returns a set
-        let sales = self.by_owner_id.get(&owner_id);
-        //get the length of that set.
-        let len = sales.map(|s| s.len()).unwrap_or_default();
-        //how much NEAR is being used up for all the current sales on the account
-        let diff = storage_per_sale().saturating_mul(u128::from(len));
+        //how much NEAR is being used up for the bytes that all the current sales on the account take up
+        let diff = env::storage_byte_cost().saturating_mul(self.storage_used_of(owner_id.clone()).0.into());
 
         //the excess to withdraw is the total storage paid - storage being used up.
         amount = amount.saturating_sub(diff);
@@ -184,6 +275,17 @@ impl Contract {
     pub fn storage_balance_of(&self, account_id: AccountId) -> NearToken {
         self.storage_deposits.get(&account_id).unwrap_or(ZERO_NEAR)
     }
+
+    //return how many bytes of storage an account's sales are using (result is a string)
+    pub fn storage_used_of(&self, account_id: AccountId) -> U64 {
+        U64(self.storage_used.get(&account_id).unwrap_or(0))
+    }
+
+    //return how much of the storage an account has paid for is not being used by its sales
+    pub fn storage_available_of(&self, account_id: AccountId) -> NearToken {
+        let used = env::storage_byte_cost().saturating_mul(self.storage_used_of(account_id.clone()).0.into());
+        self.storage_balance_of(account_id).saturating_sub(used)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,96 @@
+use crate::*;
+
+//the state of the contract before sales were keyed by (contract, token ID) pairs. Sales used to be keyed by
+//`contract ID + "." + token ID` strings, which could collide since both account IDs and token IDs can contain dots
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub sales: UnorderedMap<String, OldSale>,
+    pub by_owner_id: LookupMap<AccountId, UnorderedSet<String>>,
+    pub by_nft_contract_id: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub storage_deposits: LookupMap<AccountId, NearToken>,
+}
+
+//a sale as it was stored before the market could hold tokens in custody
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldSale {
+    pub owner_id: AccountId,
+    pub approval_id: u64,
+    pub nft_contract_id: String,
+    pub token_id: String,
+    pub sale_conditions: SalePriceInYoctoNear,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        migrate the sales from the old string keys to (contract, token ID) keys. Every sale is taken out of the
+        old collections and listed again, which also measures the storage charged to its owner. Every collection
+        that didn't exist before is created empty. This touches every sale so the GAS it needs grows with the
+        number of sales on the market.
+    */
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old_state: OldContract = env::state_read().expect("Failed to read the old state");
+
+        //take every sale out of the old collections
+        let mut sales = Vec::new();
+        for (_, old_sale) in old_state.sales.to_vec() {
+            let nft_contract_id: AccountId = old_sale.nft_contract_id.parse().unwrap();
+
+            //clear the sets for the owner and nft contract. They're rebuilt when the sale is listed again
+            if let Some(mut by_owner_id) = old_state.by_owner_id.remove(&old_sale.owner_id) {
+                by_owner_id.clear();
+            }
+            if let Some(mut by_nft_contract_id) = old_state.by_nft_contract_id.remove(&nft_contract_id) {
+                by_nft_contract_id.clear();
+            }
+
+            sales.push(Sale {
+                owner_id: old_sale.owner_id,
+                approval_id: old_sale.approval_id,
+                nft_contract_id: old_sale.nft_contract_id,
+                token_id: old_sale.token_id,
+                sale_conditions: old_sale.sale_conditions,
+                in_custody: false, //the market couldn't hold tokens before so every owner still has their token
+            });
+        }
+        old_state.sales.clear();
+
+        //the old collections are empty so the new ones can use the same prefixes
+        let mut this = Self {
+            owner_id: old_state.owner_id,
+            sales: UnorderedMap::new(StorageKey::Sales),
+            by_owner_id: LookupMap::new(StorageKey::ByOwnerId),
+            by_nft_contract_id: old_state.by_nft_contract_id,
+            storage_deposits: old_state.storage_deposits,
+            storage_used: LookupMap::new(StorageKey::StorageUsed),
+            sale_storage: LookupMap::new(StorageKey::SaleStorage),
+            auctions: LookupMap::new(StorageKey::Auctions),
+            auction_bids: LookupMap::new(StorageKey::AuctionBids),
+            swaps: UnorderedMap::new(StorageKey::Swaps),
+            swaps_by_account: LookupMap::new(StorageKey::SwapsByAccount),
+            next_swap_id: 0,
+            swap_claims: LookupMap::new(StorageKey::SwapClaims),
+            standing_offers: UnorderedMap::new(StorageKey::StandingOffers),
+            standing_offers_by_nft_contract_id: LookupMap::new(
+                StorageKey::StandingOffersByNFTContractId,
+            ),
+            next_standing_offer_id: 0,
+            rentals: UnorderedMap::new(StorageKey::Rentals),
+            layaways: LookupMap::new(StorageKey::Layaways),
+            royalty_receivers: LookupSet::new(StorageKey::RoyaltyReceivers),
+            unpaid_royalties: LookupMap::new(StorageKey::UnpaidRoyalties),
+        };
+
+        //list every sale again under its new key
+        for sale in sales {
+            this.internal_insert_sale(sale, None);
+        }
+
+        this
+    }
+}
@@ -1,4 +1,5 @@
 use crate::*;
+use near_sdk::log;
 
 /// approval callbacks from NFT Contracts
 
@@ -27,8 +28,129 @@ impl NonFungibleTokenApprovalsReceiver for Contract {
         approval_id: u64,
         msg: String,
     ) {
+        //the nft contract that the token was approved on
+        let nft_contract_id = env::predecessor_account_id();
+
+        //if the message accepts a swap, we record the approval for the requested token.
+        //once every requested token has been approved, the swap is executed
+        if let Ok(SwapApprovalMsg { swap_id }) = near_sdk::serde_json::from_str(&msg) {
+            self.internal_accept_swap_token(swap_id, nft_contract_id, token_id, owner_id, approval_id);
+        }
+
         /*
-          YOU CAN PUT SOME INTERNAL MARKETPLACE LOGIC HERE
+          YOU CAN PUT SOME MORE INTERNAL MARKETPLACE LOGIC HERE
         */
     }
 }
+
+/// transfer callbacks from NFT Contracts
+
+//the message passed into nft_transfer_call to tell the market what to do with the token it receives
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum TransferMsg {
+    //sell the token into a specific standing offer
+    AcceptOffer { offer_id: StandingOfferId },
+    //sell the token into the highest standing offer that matches it
+    AcceptBestOffer,
+    //list the token for sale and have the market hold it until it's bought or the sale is removed
+    List {
+        sale_conditions: SalePriceInYoctoNear,
+    },
+    //list the token for rent and have the market hold it whenever it isn't rented out
+    ListForRent {
+        daily_rate: NearToken,
+        max_duration_days: u32,
+        collateral: NearToken,
+    },
+    //return a rented token to the market to get the collateral back
+    ReturnRental,
+}
+
+/*
+    trait that will be used as the callback from the NFT contract. When nft_transfer_call is
+    called, the token is transferred to this marketplace and this is the function that is invoked.
+    Returning true tells the nft contract to give the token back to the previous owner.
+*/
+trait NonFungibleTokenReceiver {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool>;
+}
+
+//implementation of the trait
+#[near_bindgen]
+impl NonFungibleTokenReceiver for Contract {
+    fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: TokenId,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        //the nft contract that the token was transferred on
+        let nft_contract_id = env::predecessor_account_id();
+
+        //if we can't understand the message, the token goes back to its previous owner
+        let transfer_msg: TransferMsg = match near_sdk::serde_json::from_str(&msg) {
+            Ok(transfer_msg) => transfer_msg,
+            Err(_) => {
+                log!("Invalid msg for nft_on_transfer, returning token {}", token_id);
+                return PromiseOrValue::Value(true);
+            }
+        };
+
+        //the seller is the previous owner of the token, even if an approved account sent it
+        log!("Received token {} from {}", token_id, sender_id);
+        match transfer_msg {
+            TransferMsg::AcceptOffer { offer_id } => self.internal_sell_into_standing_offer(
+                Some(offer_id),
+                nft_contract_id,
+                token_id,
+                previous_owner_id,
+            ),
+            TransferMsg::AcceptBestOffer => self.internal_sell_into_standing_offer(
+                None,
+                nft_contract_id,
+                token_id,
+                previous_owner_id,
+            ),
+            TransferMsg::List { sale_conditions } => {
+                self.internal_list_in_custody(
+                    previous_owner_id,
+                    nft_contract_id,
+                    token_id,
+                    sale_conditions,
+                );
+                //the market keeps the token
+                PromiseOrValue::Value(false)
+            }
+            TransferMsg::ListForRent {
+                daily_rate,
+                max_duration_days,
+                collateral,
+            } => {
+                self.internal_list_for_rent(
+                    previous_owner_id,
+                    nft_contract_id,
+                    token_id,
+                    daily_rate,
+                    max_duration_days,
+                    collateral,
+                );
+                //the market keeps the token
+                PromiseOrValue::Value(false)
+            }
+            TransferMsg::ReturnRental => PromiseOrValue::Value(self.internal_return_rental(
+                nft_contract_id,
+                token_id,
+                previous_owner_id,
+            )),
+        }
+    }
+}
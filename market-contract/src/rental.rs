@@ -0,0 +1,312 @@
+use crate::*;
+use near_sdk::{is_promise_success, log, NearSchema};
+
+//GAS for resolving the transfer of a rented token
+const GAS_FOR_RESOLVE_RENTAL_TRANSFER: Gas = Gas::from_tgas(10);
+
+//the length of a rental day in milliseconds
+const DAY_IN_MS: u64 = 86_400_000;
+
+//struct that holds a token that the market holds in custody so that it can be rented out
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Rental {
+    //owner of the token that receives the rent
+    pub owner_id: AccountId,
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID for rent
+    pub token_id: TokenId,
+    //rent in yoctoNEAR that is charged for every day the token is rented
+    pub daily_rate: NearToken,
+    //the maximum number of days that the token can be rented for at once
+    pub max_duration_days: u32,
+    //collateral in yoctoNEAR that the renter has to escrow until the token is returned
+    pub collateral: NearToken,
+    //the current renter of the token. If this is None, the token is held by the market and can be rented
+    pub active: Option<ActiveRental>,
+}
+
+//the renter of a token that is currently rented out
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct ActiveRental {
+    //account that rented the token
+    pub renter_id: AccountId,
+    //collateral held in escrow until the token is returned
+    pub collateral: NearToken,
+    //rent that is paid to the owner once the token has been transferred to the renter
+    pub rent: NearToken,
+    //when the token has to be returned by, Unix epoch in milliseconds
+    pub ends_at: u64,
+}
+
+#[near_bindgen]
+impl Contract {
+    //rent a token for a number of days. The attached deposit has to cover the rent for those days plus the collateral.
+    //any excess is refunded once the token has been transferred to the renter
+    #[payable]
+    pub fn rent(&mut self, nft_contract_id: AccountId, token_id: TokenId, days: u32) {
+        //get the unique rental ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+
+        //get the rental. If the token isn't for rent, panic.
+        let mut rental = self.rentals.get(&contract_and_token_id).expect("No rental");
+        assert!(rental.active.is_none(), "Token is already rented");
+
+        //make sure the renter isn't the owner of the token
+        let renter_id = env::predecessor_account_id();
+        assert_ne!(rental.owner_id, renter_id, "Cannot rent your own token.");
+
+        //make sure the number of days is valid
+        assert!(
+            days > 0 && days <= rental.max_duration_days,
+            "Tokens can be rented for 1 to {} days",
+            rental.max_duration_days
+        );
+
+        //make sure the deposit covers the rent and the collateral
+        let deposit = env::attached_deposit();
+        let rent = rental.daily_rate.saturating_mul(days.into());
+        let required_deposit = rent.saturating_add(rental.collateral);
+        assert!(
+            deposit.ge(&required_deposit),
+            "Attached deposit must be greater than or equal to the rent plus collateral: {}. Your deposit: {}",
+            required_deposit,
+            deposit
+        );
+
+        //the token is marked as rented before it's transferred so nobody else can rent it in the meantime
+        rental.active = Some(ActiveRental {
+            renter_id: renter_id.clone(),
+            collateral: rental.collateral,
+            rent,
+            ends_at: env::block_timestamp_ms() + u64::from(days) * DAY_IN_MS,
+        });
+        self.rentals.insert(&contract_and_token_id, &rental);
+
+        //transfer the token to the renter and then resolve the rental
+        ext_contract::ext(nft_contract_id.clone())
+            .with_attached_deposit(ONE_YOCTONEAR)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer(
+                renter_id,
+                token_id.clone(),
+                None,
+                Some("rented from market".to_string()),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_RENTAL_TRANSFER)
+                    .resolve_rent(nft_contract_id, token_id, deposit),
+            );
+    }
+
+    /*
+        private method used to resolve the promise when transferring a rented token to the renter. If the transfer succeeded,
+        the owner is paid the rent and the renter gets back anything they paid above the rent and collateral. If it failed,
+        the token is still held by the market so the renter is refunded in full and the token can be rented again.
+    */
+    #[private]
+    pub fn resolve_rent(&mut self, nft_contract_id: AccountId, token_id: TokenId, deposit: NearToken) {
+        let contract_and_token_id = (nft_contract_id, token_id);
+        let mut rental = self.rentals.get(&contract_and_token_id).expect("No rental");
+        let active = rental.active.clone().expect("Token is not rented");
+
+        if is_promise_success() {
+            //pay the owner the rent and refund the renter's excess deposit
+            Promise::new(rental.owner_id).transfer(active.rent);
+            let excess = deposit.saturating_sub(active.rent.saturating_add(active.collateral));
+            if excess.gt(&ZERO_NEAR) {
+                Promise::new(active.renter_id).transfer(excess);
+            }
+        } else {
+            log!("Transferring rented token {} failed", rental.token_id);
+            rental.active = None;
+            self.rentals.insert(&contract_and_token_id, &rental);
+            Promise::new(active.renter_id).transfer(deposit);
+        }
+    }
+
+    //claim the collateral for a token that wasn't returned before the deadline. The renter keeps the token
+    //and the rental is removed from the market.
+    #[payable]
+    pub fn claim_rental_collateral(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //get the rental. If the token isn't for rent, panic.
+        let rental = self
+            .rentals
+            .get(&(nft_contract_id.clone(), token_id.clone()))
+            .expect("No rental");
+        assert_eq!(
+            env::predecessor_account_id(),
+            rental.owner_id,
+            "Must be rental owner"
+        );
+
+        //the collateral can only be claimed once the deadline has passed
+        let active = rental.active.expect("Token is not rented");
+        assert!(
+            env::block_timestamp_ms() >= active.ends_at,
+            "The rental has not ended"
+        );
+
+        //remove the rental and pay the owner the collateral
+        let rental = self.internal_remove_rental(nft_contract_id, token_id);
+        Promise::new(rental.owner_id).transfer(active.collateral);
+    }
+
+    //take a token that isn't rented out off the market and give it back to its owner
+    #[payable]
+    pub fn remove_rental(&mut self, nft_contract_id: AccountId, token_id: TokenId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //remove the rental. If the token isn't for rent, panic.
+        let rental = self.internal_remove_rental(nft_contract_id, token_id);
+        //make sure the caller is the owner and nobody is renting the token. If this fails, the removal will revert
+        assert_eq!(
+            env::predecessor_account_id(),
+            rental.owner_id,
+            "Must be rental owner"
+        );
+        assert!(rental.active.is_none(), "Token is rented");
+
+        //give the token back to the owner. If the transfer fails, the rental is listed again
+        ext_contract::ext(rental.nft_contract_id.clone())
+            .with_attached_deposit(ONE_YOCTONEAR)
+            .with_static_gas(GAS_FOR_NFT_TRANSFER)
+            .nft_transfer(
+                rental.owner_id.clone(),
+                rental.token_id.clone(),
+                None,
+                Some("returned from market custody".to_string()),
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_RETURN_FROM_CUSTODY)
+                    .resolve_remove_rental(rental),
+            );
+    }
+
+    //private method used to resolve the promise when giving a rented token back to its owner.
+    //if the transfer failed, the market still holds the token so it's listed for rent again
+    #[private]
+    pub fn resolve_remove_rental(&mut self, rental: Rental) {
+        if !is_promise_success() {
+            log!("Returning token {} from custody failed", rental.token_id);
+            self.internal_insert_rental(rental);
+        }
+    }
+}
+
+impl Contract {
+    //list a token that was just transferred to the market for rent. If this panics, the nft contract gives the token back
+    pub(crate) fn internal_list_for_rent(
+        &mut self,
+        owner_id: AccountId,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        daily_rate: NearToken,
+        max_duration_days: u32,
+        collateral: NearToken,
+    ) {
+        assert!(max_duration_days > 0, "Max duration must be at least 1 day");
+
+        self.internal_insert_rental(Rental {
+            owner_id: owner_id.clone(),
+            nft_contract_id,
+            token_id,
+            daily_rate,
+            max_duration_days,
+            collateral,
+            active: None,
+        });
+
+        //the held token is charged for storage like any other listing
+        self.assert_storage_covered(&owner_id);
+    }
+
+    //handle a rented token being transferred back to the market. Returns true if the token should go back to the sender
+    pub(crate) fn internal_return_rental(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        previous_owner_id: AccountId,
+    ) -> bool {
+        let contract_and_token_id = (nft_contract_id, token_id);
+
+        //only the renter of a token that is rented out can return it
+        let mut rental = match self.rentals.get(&contract_and_token_id) {
+            Some(rental) => rental,
+            None => {
+                log!("No rental for token {}", contract_and_token_id.1);
+                return true;
+            }
+        };
+        let active = match rental.active.take() {
+            Some(active) if active.renter_id == previous_owner_id => active,
+            _ => {
+                log!("Token {} is not rented by {}", contract_and_token_id.1, previous_owner_id);
+                return true;
+            }
+        };
+
+        //the renter gets the collateral back if the token was returned in time. Otherwise it goes to the owner
+        if env::block_timestamp_ms() < active.ends_at {
+            Promise::new(active.renter_id).transfer(active.collateral);
+        } else {
+            Promise::new(rental.owner_id.clone()).transfer(active.collateral);
+        }
+
+        //the market holds the token again so it can be rented out again
+        self.rentals.insert(&contract_and_token_id, &rental);
+        false
+    }
+
+    //internal method for inserting a rental. The storage used by the rental is charged to its owner
+    pub(crate) fn internal_insert_rental(&mut self, rental: Rental) {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let contract_and_token_id = (rental.nft_contract_id.clone(), rental.token_id.clone());
+        assert!(
+            self.rentals.insert(&contract_and_token_id, &rental).is_none(),
+            "Token is already listed for rent"
+        );
+
+        //charge the owner for the storage the rental actually uses
+        let required_storage = env::storage_usage().saturating_sub(initial_storage_usage);
+        self.internal_charge_storage(&rental.owner_id, required_storage);
+    }
+
+    //internal method for removing a rental. This returns the removed rental and releases its storage
+    pub(crate) fn internal_remove_rental(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+    ) -> Rental {
+        let contract_and_token_id = (nft_contract_id, token_id);
+        let mut rental = self.rentals.get(&contract_and_token_id).expect("No rental");
+
+        //the owner was charged for the rental without a renter, so the renter is cleared before measuring
+        let active = rental.active.take();
+        self.rentals.insert(&contract_and_token_id, &rental);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        self.rentals.remove(&contract_and_token_id);
+
+        //release the storage that was freed up by removing the rental so the owner can withdraw it
+        let released_storage = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_release_storage(&rental.owner_id, released_storage);
+
+        rental.active = active;
+        rental
+    }
+}
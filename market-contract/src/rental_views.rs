@@ -0,0 +1,32 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// views
+
+    //get the rental information for a given nft contract and token ID
+    pub fn get_rental(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<Rental> {
+        self.rentals.get(&(nft_contract_id, token_id))
+    }
+
+    //returns the number of tokens listed for rent on the marketplace (as a string)
+    pub fn get_supply_rentals(&self) -> U64 {
+        U64(self.rentals.len())
+    }
+
+    //returns paginated rentals on the marketplace (result is a vector of rentals)
+    pub fn get_rentals(&self, from_index: Option<U128>, limit: Option<u32>) -> Vec<Rental> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the rentals
+        self.rentals
+            .values()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            //since we turned the values into an iterator, we need to turn it back into a vector to return
+            .collect()
+    }
+}
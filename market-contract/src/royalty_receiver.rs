@@ -0,0 +1,158 @@
+use crate::*;
+use near_sdk::{is_promise_success, log, NearSchema};
+
+//GAS for notifying a royalty receiver contract and for resolving the notification
+const GAS_FOR_ROYALTY_NOTIFICATION: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_ROYALTY_NOTIFICATION: Gas = Gas::from_tgas(5);
+//GAS that has to be left after a notification is started so the rest of the payout can still be paid
+const GAS_FOR_REMAINING_PAYOUT: Gas = Gas::from_tgas(5);
+
+//the details of the sale that a royalty receiver contract is notified with
+#[derive(Serialize, Deserialize, NearSchema, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleContext {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //the account that bought the token
+    pub buyer_id: AccountId,
+    //the price the token was sold for
+    pub price: NearToken,
+}
+
+//the method that royalty receiver contracts implement. The amount is attached to the call
+#[ext_contract(ext_royalty_receiver)]
+trait ExtRoyaltyReceiver {
+    fn on_royalty_received(&mut self, token_id: TokenId, amount: NearToken, sale_context: SaleContext);
+}
+
+#[near_bindgen]
+impl Contract {
+    //register the caller as a royalty receiver contract. Instead of a plain transfer, the caller's share of every
+    //payout is attached to a call to its on_royalty_received method. Any deposit attached is added to the caller's
+    //storage balance, which has to cover the storage used by the registration
+    #[payable]
+    pub fn register_royalty_receiver(&mut self) {
+        let receiver_id = env::predecessor_account_id();
+
+        //add the attached deposit to the receiver's storage balance
+        let deposit = env::attached_deposit();
+        if deposit.gt(&ZERO_NEAR) {
+            let balance = self.storage_deposits.get(&receiver_id).unwrap_or(ZERO_NEAR);
+            self.storage_deposits
+                .insert(&receiver_id, &balance.saturating_add(deposit));
+        }
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        assert!(
+            self.royalty_receivers.insert(&receiver_id),
+            "Already registered as a royalty receiver"
+        );
+
+        //charge the receiver for the storage the registration uses. If it isn't covered, the registration reverts
+        let required_storage = env::storage_usage().saturating_sub(initial_storage_usage);
+        self.internal_charge_storage(&receiver_id, required_storage);
+        self.assert_storage_covered(&receiver_id);
+    }
+
+    //stop receiving royalties through on_royalty_received. The storage used by the registration is released
+    //so the caller can withdraw it
+    #[payable]
+    pub fn unregister_royalty_receiver(&mut self) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let receiver_id = env::predecessor_account_id();
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        assert!(
+            self.royalty_receivers.remove(&receiver_id),
+            "Not registered as a royalty receiver"
+        );
+
+        let released_storage = initial_storage_usage.saturating_sub(env::storage_usage());
+        self.internal_release_storage(&receiver_id, released_storage);
+    }
+
+    //check if an account is notified with on_royalty_received when it's paid out
+    pub fn is_royalty_receiver(&self, account_id: AccountId) -> bool {
+        self.royalty_receivers.contains(&account_id)
+    }
+
+    /*
+        private method used to resolve the notification of a royalty receiver contract. If the call failed, the
+        amount attached to it is refunded to the market, but that refund is its own receipt and it isn't guaranteed
+        to arrive before this callback runs. Until it does, the market's balance only holds other people's escrow,
+        so nothing is paid here. The amount is set aside for the receiver to claim with claim_unpaid_royalties,
+        which is paid out of the refund.
+    */
+    #[private]
+    pub fn resolve_royalty_notification(&mut self, receiver_id: AccountId, amount: NearToken) {
+        if is_promise_success() {
+            return;
+        }
+
+        log!("Notifying royalty receiver {} failed", receiver_id);
+        let unpaid = self.unpaid_royalties.get(&receiver_id).unwrap_or(ZERO_NEAR);
+        self.unpaid_royalties
+            .insert(&receiver_id, &unpaid.saturating_add(amount));
+    }
+
+    //claim the royalties that couldn't be paid when notifying the caller failed. Only the amount that was set aside
+    //for the caller is paid
+    #[payable]
+    pub fn claim_unpaid_royalties(&mut self) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let receiver_id = env::predecessor_account_id();
+        let unpaid = self
+            .unpaid_royalties
+            .remove(&receiver_id)
+            .expect("No unpaid royalties");
+        Promise::new(receiver_id).transfer(unpaid)
+    }
+
+    //get the royalties that are waiting to be claimed by an account
+    pub fn get_unpaid_royalties(&self, account_id: AccountId) -> NearToken {
+        self.unpaid_royalties.get(&account_id).unwrap_or(ZERO_NEAR)
+    }
+}
+
+impl Contract {
+    //pay every account in the payout. Registered royalty receivers are notified with the amount attached.
+    //if there isn't enough GAS left to notify a receiver, it's paid with a plain transfer so the sale still goes through
+    pub(crate) fn internal_distribute_payout(
+        &self,
+        payout: HashMap<AccountId, NearToken>,
+        token_id: &TokenId,
+        sale_context: &SaleContext,
+    ) {
+        let gas_for_notification =
+            GAS_FOR_ROYALTY_NOTIFICATION.saturating_add(GAS_FOR_RESOLVE_ROYALTY_NOTIFICATION);
+
+        for (receiver_id, amount) in payout {
+            let remaining_gas = env::prepaid_gas().saturating_sub(env::used_gas());
+            let can_notify = amount.gt(&ZERO_NEAR)
+                && self.royalty_receivers.contains(&receiver_id)
+                && remaining_gas >= gas_for_notification.saturating_add(GAS_FOR_REMAINING_PAYOUT);
+
+            if can_notify {
+                ext_royalty_receiver::ext(receiver_id.clone())
+                    .with_attached_deposit(amount)
+                    .with_static_gas(GAS_FOR_ROYALTY_NOTIFICATION)
+                    .on_royalty_received(token_id.clone(), amount, sale_context.clone())
+                    .then(
+                        Self::ext(env::current_account_id())
+                            .with_static_gas(GAS_FOR_RESOLVE_ROYALTY_NOTIFICATION)
+                            .resolve_royalty_notification(receiver_id, amount),
+                    );
+            } else {
+                Promise::new(receiver_id).transfer(amount);
+            }
+        }
+    }
+}
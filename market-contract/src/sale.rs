@@ -1,9 +1,8 @@
 use crate::*;
-use near_sdk::serde_json::json;
-use near_sdk::{log, promise_result_as_success, NearSchema, PromiseError};
+use near_sdk::{is_promise_success, log, promise_result_as_success, NearSchema, PromiseError};
 
 //struct that holds important information about each sale on the market
-#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema)]
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
 #[borsh(crate = "near_sdk::borsh")]
 #[serde(crate = "near_sdk::serde")]
 pub struct Sale {
@@ -17,6 +16,9 @@ pub struct Sale {
     pub token_id: String,
     //sale price in yoctoNEAR that the token is listed for
     pub sale_conditions: SalePriceInYoctoNear,
+    //whether the market holds the token itself. Tokens in custody were sent to the market with nft_transfer_call
+    //and are transferred out by the market when they're bought or the sale is removed
+    pub in_custody: bool,
 }
 
 //The Json token is what will be returned from view calls.
@@ -25,6 +27,8 @@ pub struct Sale {
 pub struct JsonToken {
     //owner of the token
     pub owner_id: AccountId,
+    //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID
+    pub approved_account_ids: HashMap<AccountId, u64>,
 }
 
 #[near_bindgen]
@@ -40,45 +44,14 @@ impl Contract {
     ) {
         let owner_id = env::predecessor_account_id();
 
-        //we need to enforce that the user has enough storage for 1 EXTRA sale.
-
-        //get the storage for a sale
-        let storage_amount = self.storage_minimum_balance();
-        //get the total storage paid by the owner
-        let owner_paid_storage = self.storage_deposits.get(&owner_id).unwrap_or(ZERO_NEAR);
-        //get the storage required which is simply the storage for the number of sales they have + 1
-        let signer_storage_required = storage_amount
-            .saturating_mul((self.get_supply_by_owner_id(owner_id.clone()).0 + 1).into());
-
-        //make sure that the total paid is >= the required storage
-        assert!(
-            owner_paid_storage.ge(&signer_storage_required),
-            "Insufficient storage paid: {}, for {} sales at {} rate of per sale",
-            owner_paid_storage,
-            signer_storage_required.saturating_div(storage_per_sale().as_yoctonear()),
-            storage_per_sale()
-        );
-
-        let nft_token_promise = Promise::new(nft_contract_id.clone()).function_call(
-            "nft_token".to_owned(),
-            json!({ "token_id": token_id }).to_string().into_bytes(),
-            ZERO_NEAR,
-            Gas::from_gas(10u64.pow(13)),
-        );
-        let nft_is_approved_promise = Promise::new(nft_contract_id.clone()).function_call(
-          "nft_is_approved".to_owned(),
-          json!({ "token_id": token_id, "approved_account_id": env::current_account_id(), "approval_id": approval_id }).to_string().into_bytes(),
-          ZERO_NEAR,
-          Gas::from_gas(10u64.pow(13))
-        );
-        nft_token_promise.and(nft_is_approved_promise).then(
-            Self::ext(env::current_account_id()).process_listing(
-                owner_id.clone(),
-                nft_contract_id,
-                token_id,
-                approval_id,
-                sale_conditions,
-            ),
+        //verify the token with the nft contract and list it once the checks pass
+        self.internal_list_nft(
+            owner_id,
+            nft_contract_id,
+            token_id,
+            approval_id,
+            sale_conditions,
+            None,
         );
     }
 
@@ -87,12 +60,21 @@ impl Contract {
     pub fn remove_sale(&mut self, nft_contract_id: AccountId, token_id: String) {
         //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
         assert_one_yocto();
+        //an auction can't be taken down once bidders have escrowed their deposits. It has to be settled instead
+        self.assert_no_auction_bids(&nft_contract_id, &token_id);
+        //a sale can't be taken down while a buyer is paying for it in installments
+        self.assert_not_reserved(&nft_contract_id, &token_id);
         //get the sale object as the return value from removing the sale internally
         let sale = self.internal_remove_sale(nft_contract_id.into(), token_id);
         //get the predecessor of the call and make sure they're the owner of the sale
         let owner_id = env::predecessor_account_id();
         //if this fails, the remove sale will revert
         assert_eq!(owner_id, sale.owner_id, "Must be sale owner");
+
+        //if the market holds the token, we give it back to the owner
+        if sale.in_custody {
+            self.internal_return_from_custody(sale);
+        }
     }
 
     //updates the price for a sale on the market
@@ -103,11 +85,16 @@ impl Contract {
 
         //create the unique sale ID from the nft contract and token
         let contract_id: AccountId = nft_contract_id.into();
-        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
+        let contract_and_token_id = (contract_id.clone(), token_id.clone());
 
         //get the sale object from the unique sale ID. If there is no token, panic.
         let mut sale = self.sales.get(&contract_and_token_id).expect("No sale");
 
+        //the reserve price of an auction can't change once bidders have committed to it
+        self.assert_no_auction_bids(&contract_id, &token_id);
+        //the price can't change while a buyer is paying for the sale in installments
+        self.assert_not_reserved(&contract_id, &token_id);
+
         //assert that the caller of the function is the sale owner
         assert_eq!(
             env::predecessor_account_id(),
@@ -133,12 +120,20 @@ impl Contract {
 
         //convert the nft_contract_id from a AccountId to an AccountId
         let contract_id: AccountId = nft_contract_id.into();
-        //get the unique sale ID (contract + DELIMITER + token ID)
-        let contract_and_token_id = format!("{}{}{}", contract_id, DELIMETER, token_id);
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (contract_id.clone(), token_id.clone());
 
         //get the sale object from the unique sale ID. If the sale doesn't exist, panic.
         let sale = self.sales.get(&contract_and_token_id).expect("No sale");
 
+        //sealed-bid auctions can only be won through commit_bid and reveal_bid
+        assert!(
+            self.auctions.get(&contract_and_token_id).is_none(),
+            "Sale is a sealed-bid auction. Use commit_bid instead"
+        );
+        //a reserved sale can only be bought by the buyer paying for it in installments
+        self.assert_not_reserved(&contract_id, &token_id);
+
         //get the buyer ID which is the person who called the function and make sure they're not the owner of the sale
         let buyer_id = env::predecessor_account_id();
         assert_ne!(sale.owner_id, buyer_id, "Cannot bid on your own sale.");
@@ -162,27 +157,16 @@ impl Contract {
         price: NearToken,
         buyer_id: AccountId,
     ) -> Promise {
-        //get the sale object by removing the sale
-        let sale = self.internal_remove_sale(nft_contract_id.clone(), token_id.clone());
-
-        //initiate a cross contract call to the nft contract. This will transfer the token to the buyer and return
-        //a payout object used for the market to distribute funds to the appropriate accounts.
-        ext_contract::ext(nft_contract_id)
-            // Attach 1 yoctoNEAR with static GAS equal to the GAS for nft transfer. Also attach an unused GAS weight of 1 by default.
-            .with_attached_deposit(ONE_YOCTONEAR)
-            .with_static_gas(GAS_FOR_NFT_TRANSFER)
-            .nft_transfer_payout(
-                buyer_id.clone(),                 //purchaser (person to transfer the NFT to)
-                token_id,                         //token ID to transfer
-                sale.approval_id, //market contract's approval ID in order to transfer the token on behalf of the owner
-                "payout from market".to_string(), //memo (to include some context)
-                /*
-                    the price that the token was purchased for. This will be used in conjunction with the royalty percentages
-                    for the token in order to determine how much money should go to which account.
-                */
-                price,
-                10, //the maximum amount of accounts the market can payout at once (this is limited by GAS)
-            )
+        //remove the sale and initiate the transfer. If the market holds the token, the resolve needs the sale
+        //to pay the seller or relist the token
+        let (transfer_payout_promise, custody_sale) = self.internal_transfer_payout(
+            nft_contract_id.clone(),
+            token_id.clone(),
+            price,
+            buyer_id.clone(),
+        );
+
+        transfer_payout_promise
             //after the transfer payout has been initiated, we resolve the promise by calling our own resolve_purchase function.
             //resolve purchase will take the payout object returned from the nft_transfer_payout and actually pay the accounts
             .then(
@@ -192,6 +176,9 @@ impl Contract {
                     .resolve_purchase(
                         buyer_id, //the buyer and price are passed in incase something goes wrong and we need to refund the buyer
                         price,
+                        custody_sale,
+                        nft_contract_id, //the token is passed in so royalty receiver contracts know what was sold
+                        token_id,
                     ),
             )
     }
@@ -200,60 +187,48 @@ impl Contract {
         private method used to resolve the promise when calling nft_transfer_payout. This will take the payout object and
         check to see if it's authentic and there's no problems. If everything is fine, it will pay the accounts. If there's a problem,
         it will refund the buyer for the price.
+
+        If the market held the token, the custody sale is passed in. The owner's share of the payout is then keyed by the
+        market so it's credited to the seller, and if something went wrong the token is still held so it's listed again.
     */
     #[private]
-    pub fn resolve_purchase(&mut self, buyer_id: AccountId, price: NearToken) -> NearToken {
-        // checking for payout information returned from the nft_transfer_payout method
-        let payout_option = promise_result_as_success().and_then(|value| {
-            //if we set the payout_option to None, that means something went wrong and we should refund the buyer
-            near_sdk::serde_json::from_slice::<Payout>(&value)
-                //converts the result to an optional value
-                .ok()
-                //returns None if the none. Otherwise executes the following logic
-                .and_then(|payout_object| {
-                    //we'll check if length of the payout object is > 10 or it's empty. In either case, we return None
-                    if payout_object.payout.len() > 10 || payout_object.payout.is_empty() {
-                        env::log_str("Cannot have more than 10 royalties");
-                        None
-
-                    //if the payout object is the correct length, we move forward
-                    } else {
-                        //we'll keep track of how much the nft contract wants us to payout. Starting at the full price payed by the buyer
-                        let mut remainder = price;
-
-                        //loop through the payout and subtract the values from the remainder.
-                        for &value in payout_object.payout.values() {
-                            //checked sub checks for overflow or any errors and returns None if there are problems
-                            remainder = remainder.checked_sub(value)?;
-                        }
-                        //Check to see if the NFT contract sent back a faulty payout that requires us to pay more or too little.
-                        //The remainder will be 0 if the payout summed to the total price. The remainder will be 1 if the royalties
-                        //we something like 3333 + 3333 + 3333.
-                        if remainder.eq(&ZERO_NEAR) || remainder.eq(&NearToken::from_yoctonear(1)) {
-                            //set the payout_option to be the payout because nothing went wrong
-                            Some(payout_object.payout)
-                        } else {
-                            //if the remainder was anything but 1 or 0, we return None
-                            None
-                        }
-                    }
-                })
-        });
+    pub fn resolve_purchase(
+        &mut self,
+        buyer_id: AccountId,
+        price: NearToken,
+        custody_sale: Option<Sale>,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+    ) -> NearToken {
+        // checking for payout information returned from the nft_transfer_payout method.
+        //if we set the payout_option to None, that means something went wrong and we should refund the buyer
+        let payout_option =
+            promise_result_as_success().and_then(|value| parse_payout(&value, price));
 
         // if the payout option was some payout, we set this payout variable equal to that some payout
-        let payout = if let Some(payout_option) = payout_option {
+        let payout = if let Some(mut payout_option) = payout_option {
+            if let Some(sale) = &custody_sale {
+                credit_market_share_to_seller(&mut payout_option, &sale.owner_id);
+            }
             payout_option
         //if the payout option was None, we refund the buyer for the price they payed and return
         } else {
-            Promise::new(buyer_id).transfer(price);
+            Promise::new(buyer_id.clone()).transfer(price);
+            //the market still holds the token so we put the sale back up
+            if let Some(sale) = custody_sale {
+                self.internal_insert_sale(sale, None);
+            }
             // leave function and return the price that was refunded
             return price;
         };
 
-        // NEAR payouts
-        for (receiver_id, amount) in payout {
-            Promise::new(receiver_id).transfer(amount);
-        }
+        // NEAR payouts. Royalty receiver contracts are notified with the sale
+        let sale_context = SaleContext {
+            nft_contract_id,
+            buyer_id,
+            price,
+        };
+        self.internal_distribute_payout(payout, &token_id, &sale_context);
 
         //return the price payout out
         price
@@ -267,66 +242,59 @@ impl Contract {
         token_id: TokenId,
         approval_id: u64,
         sale_conditions: SalePriceInYoctoNear,
-        #[callback_result] nft_token_result: Result<JsonToken, PromiseError>,
+        auction: Option<SealedBidAuction>,
+        #[callback_result] nft_token_result: Result<Option<JsonToken>, PromiseError>,
         #[callback_result] nft_is_approved_result: Result<bool, PromiseError>,
     ) {
-        if let Ok(result) = nft_token_result {
-            assert_eq!(result.owner_id, owner_id, "Signer is not NFT owner",)
-        } else {
-            log!("nft_is_approved call failed");
-        }
-        if let Ok(result) = nft_is_approved_result {
-            assert_eq!(result, true, "Marketplace contract is not approved",)
-        } else {
-            log!("nft_is_approved call failed");
-        }
+        //the listing fails unless both calls to the nft contract succeeded. If either check panics, nothing is listed
+        let token = nft_token_result
+            .unwrap_or_else(|_| env::panic_str("nft_token call failed"))
+            //the nft contract returns null if the token doesn't exist
+            .unwrap_or_else(|| env::panic_str("Token does not exist"));
+        let is_approved = nft_is_approved_result
+            .unwrap_or_else(|_| env::panic_str("nft_is_approved call failed"));
+
+        //make sure the token belongs to the seller and the market is approved to transfer it
+        assert_eq!(token.owner_id, owner_id, "Signer is not NFT owner");
+        assert!(is_approved, "Marketplace contract is not approved");
+
+        //make sure the approval ID the seller passed in is the market's current approval. If the owner approved
+        //the market again, the old approval ID can no longer be used to transfer the token
+        assert_eq!(
+            token.approved_account_ids.get(&env::current_account_id()),
+            Some(&approval_id),
+            "Approval ID {} is not the marketplace's current approval",
+            approval_id
+        );
 
-        //create the unique sale ID which is the contract + DELIMITER + token ID
-        let contract_and_token_id = format!("{}{}{}", nft_contract_id, DELIMETER, token_id);
+        //make sure the token wasn't listed while the nft contract was being checked
+        self.assert_not_listed(&nft_contract_id, &token_id);
 
-        //insert the key value pair into the sales map. Key is the unique ID. value is the sale object
-        self.sales.insert(
-            &contract_and_token_id,
-            &Sale {
+        //insert the sale object and populate the collections necessary for the view calls
+        self.internal_insert_sale(
+            Sale {
                 owner_id: owner_id.clone(),                   //owner of the sale / token
                 approval_id, //approval ID for that token that was given to the market
                 nft_contract_id: nft_contract_id.to_string(), //NFT contract the token was minted on
-                token_id: token_id.clone(), //the actual token ID
-                sale_conditions, //the sale conditions
+                token_id,                                     //the actual token ID
+                sale_conditions,                              //the sale conditions
+                in_custody: false, //the owner keeps the token and the market transfers it with its approval
             },
+            auction,
         );
 
-        //Extra functionality that populates collections necessary for the view calls
-
-        //get the sales by owner ID for the given owner. If there are none, we create a new empty set
-        let mut by_owner_id = self.by_owner_id.get(&owner_id).unwrap_or_else(|| {
-            UnorderedSet::new(StorageKey::ByOwnerIdInner {
-                //we get a new unique prefix for the collection by hashing the owner
-                account_id_hash: hash_account_id(&owner_id),
-            })
-        });
-
-        //insert the unique sale ID into the set
-        by_owner_id.insert(&contract_and_token_id);
-        //insert that set back into the collection for the owner
-        self.by_owner_id.insert(&owner_id, &by_owner_id);
-
-        //get the token IDs for the given nft contract ID. If there are none, we create a new empty set
-        let mut by_nft_contract_id = self
-            .by_nft_contract_id
-            .get(&nft_contract_id)
-            .unwrap_or_else(|| {
-                UnorderedSet::new(StorageKey::ByNFTContractIdInner {
-                    //we get a new unique prefix for the collection by hashing the owner
-                    account_id_hash: hash_account_id(&nft_contract_id),
-                })
-            });
-
-        //insert the token ID into the set
-        by_nft_contract_id.insert(&token_id);
-        //insert the set back into the collection for the given nft contract ID
-        self.by_nft_contract_id
-            .insert(&nft_contract_id, &by_nft_contract_id);
+        //make sure the owner has paid for the storage the sale uses. If this fails, the listing will revert
+        self.assert_storage_covered(&owner_id);
+    }
+
+    //private method used to resolve the promise when giving a token held in custody back to its owner.
+    //if the transfer failed, the market still holds the token so it's listed again
+    #[private]
+    pub fn resolve_return_from_custody(&mut self, sale: Sale) {
+        if !is_promise_success() {
+            log!("Returning token {} from custody failed", sale.token_id);
+            self.internal_insert_sale(sale, None);
+        }
     }
 }
 
@@ -338,5 +306,12 @@ impl Contract {
 */
 #[ext_contract(ext_self)]
 trait ExtSelf {
-    fn resolve_purchase(&mut self, buyer_id: AccountId, price: NearToken) -> Promise;
+    fn resolve_purchase(
+        &mut self,
+        buyer_id: AccountId,
+        price: NearToken,
+        custody_sale: Option<Sale>,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+    ) -> Promise;
 }
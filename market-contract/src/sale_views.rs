@@ -107,16 +107,51 @@ impl Contract {
             .skip(start as usize) 
             //take the first "limit" elements in the vector. If we didn't specify a limit, use 0
             .take(limit.unwrap_or(0) as usize) 
-            //we'll map the token IDs which are strings into Sale objects by passing in the unique sale ID (contract + DELIMITER + token ID)
-            .map(|token_id| self.sales.get(&format!("{}{}{}", nft_contract_id, DELIMETER, token_id)).unwrap())
+            //we'll map the token IDs which are strings into Sale objects by passing in the unique sale ID (contract, token ID)
+            .map(|token_id| self.sales.get(&(nft_contract_id.clone(), token_id.clone())).unwrap())
             //since we turned the keys into an iterator, we need to turn it back into a vector to return
             .collect()
     }
 
-    //get a sale information for a given unique sale ID (contract + DELIMITER + token ID)
-    pub fn get_sale(&self, nft_contract_token: ContractAndTokenId) -> Option<Sale> {
+    //get a sale information for a given nft contract and token ID
+    pub fn get_sale(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<Sale> {
         //try and get the sale object for the given unique sale ID. Will return an option since
-        //we're not guaranteed that the unique sale ID passed in will be valid.
-        self.sales.get(&nft_contract_token)
+        //we're not guaranteed that the token passed in is for sale.
+        self.sales.get(&(nft_contract_id, token_id))
+    }
+
+    //get the auction information for a sale that was listed as a sealed-bid auction
+    pub fn get_auction(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<JsonAuction> {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id, token_id);
+
+        //if there is no auction for the sale, we return None
+        let auction = self.auctions.get(&contract_and_token_id)?;
+        Some(JsonAuction {
+            sale: self.sales.get(&contract_and_token_id).unwrap(),
+            commit_ends_at: auction.commit_ends_at,
+            reveal_ends_at: auction.reveal_ends_at,
+            bids: self
+                .auction_bids
+                .get(&contract_and_token_id)
+                .unwrap_or_default(),
+        })
+    }
+
+    //get the layaway terms and current reservation for a sale that can be bought in installments
+    pub fn get_layaway(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<JsonLayaway> {
+        //get the unique sale ID (contract, token ID)
+        let contract_and_token_id = (nft_contract_id, token_id);
+
+        //if there are no layaway terms for the sale, we return None
+        let layaway = self.layaways.get(&contract_and_token_id)?;
+        let sale = self.sales.get(&contract_and_token_id).unwrap();
+        Some(JsonLayaway {
+            amount_due: layaway.amount_due(sale.sale_conditions, env::block_timestamp_ms()),
+            deadline: layaway.deadline(),
+            sale,
+            terms: layaway.terms,
+            reservation: layaway.reservation,
+        })
     }
 }
@@ -0,0 +1,300 @@
+use crate::*;
+use near_sdk::{log, NearSchema, PromiseResult};
+
+//nft_on_transfer only gets the GAS that the nft contract attached to it (25 TGas for the tutorial contracts),
+//so selling into a standing offer has to fit within that
+const GAS_FOR_OFFER_TRANSFER_PAYOUT: Gas = Gas::from_tgas(10);
+const GAS_FOR_RESOLVE_OFFER_SALE: Gas = Gas::from_tgas(8);
+//the most standing offers an nft contract can have. Finding the best offer reads every one of them within
+//the GAS given to nft_on_transfer, so this is limited by GAS
+pub(crate) const MAX_STANDING_OFFERS_PER_NFT_CONTRACT: u64 = 20;
+
+pub type StandingOfferId = u64;
+
+//struct that holds a standing offer to buy a token. The offered NEAR is held in escrow by the market
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct StandingOffer {
+    //account that made the offer and will receive the token
+    pub buyer_id: AccountId,
+    //nft contract the offer is for
+    pub nft_contract_id: AccountId,
+    //the token the offer is for. If this is None, the offer is a collection bid for any token on the nft contract
+    pub token_id: Option<TokenId>,
+    //amount of NEAR held in escrow for the offer
+    pub amount: NearToken,
+}
+
+//The Json standing offer is what will be returned from view calls.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonStandingOffer {
+    //unique ID of the offer
+    pub offer_id: StandingOfferId,
+    //the standing offer
+    pub offer: StandingOffer,
+}
+
+impl StandingOffer {
+    //whether the offer can buy the given token
+    pub(crate) fn matches(&self, nft_contract_id: &AccountId, token_id: &TokenId) -> bool {
+        &self.nft_contract_id == nft_contract_id
+            && self.token_id.as_ref().map_or(true, |offer_token_id| offer_token_id == token_id)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //make a standing offer for a specific token, or for any token on an nft contract if no token ID is passed in.
+    //The attached deposit is the amount offered and is held in escrow until the offer is accepted or withdrawn.
+    //If the nft contract already has the most offers it can, the new offer has to beat the lowest one, which is refunded
+    #[payable]
+    pub fn make_standing_offer(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: Option<TokenId>,
+    ) -> StandingOfferId {
+        let amount = env::attached_deposit();
+
+        //make room for the offer by outbidding the lowest offer if the nft contract is full
+        self.internal_evict_lowest_standing_offer(&nft_contract_id, amount);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //get a new unique offer ID and insert the offer
+        let offer_id = self.next_standing_offer_id;
+        self.next_standing_offer_id += 1;
+        self.internal_insert_standing_offer(
+            offer_id,
+            &StandingOffer {
+                buyer_id: env::predecessor_account_id(),
+                nft_contract_id,
+                token_id,
+                amount,
+            },
+        );
+
+        //the escrowed amount also pays for the storage of the offer while it's held
+        let required_cost = env::storage_byte_cost().saturating_mul(
+            env::storage_usage()
+                .saturating_sub(initial_storage_usage)
+                .into(),
+        );
+        assert!(
+            amount.ge(&required_cost),
+            "Standing offers must be at least {}",
+            required_cost
+        );
+
+        offer_id
+    }
+
+    //withdraw a standing offer and get the escrowed NEAR back
+    #[payable]
+    pub fn withdraw_standing_offer(&mut self, offer_id: StandingOfferId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //remove the offer. If it doesn't exist, panic.
+        let offer = self.internal_remove_standing_offer(offer_id);
+        //make sure the caller made the offer. If this fails, the removal will revert
+        assert_eq!(
+            env::predecessor_account_id(),
+            offer.buyer_id,
+            "Must be the offer's buyer"
+        );
+
+        //refund the escrowed NEAR
+        Promise::new(offer.buyer_id).transfer(offer.amount);
+    }
+
+    /*
+        private method used to resolve the promise when selling a token held by the market into a standing offer.
+        If the token was transferred, the seller and any royalty holders are paid out of escrow. If the transfer
+        failed, the offer is put back and we return true so that the nft contract returns the token to the seller.
+    */
+    #[private]
+    pub fn resolve_standing_offer_sale(
+        &mut self,
+        seller_id: AccountId,
+        offer_id: StandingOfferId,
+        offer: StandingOffer,
+        token_id: TokenId,
+    ) -> bool {
+        match env::promise_result(0) {
+            PromiseResult::Successful(value) => {
+                //the token is now owned by the buyer so the escrow goes to the seller and royalty holders.
+                //if the payout isn't authentic, the seller simply gets the full amount
+                let payout = if let Some(mut payout) = parse_payout(&value, offer.amount) {
+                    credit_market_share_to_seller(&mut payout, &seller_id);
+                    payout
+                } else {
+                    HashMap::from([(seller_id, offer.amount)])
+                };
+                let sale_context = SaleContext {
+                    nft_contract_id: offer.nft_contract_id.clone(),
+                    buyer_id: offer.buyer_id.clone(),
+                    price: offer.amount,
+                };
+                self.internal_distribute_payout(payout, &token_id, &sale_context);
+                false
+            }
+            _ => {
+                //the token never left the market. Put the offer back and ask the nft contract to return the token
+                log!("Selling into standing offer {} failed", offer_id);
+                self.internal_insert_standing_offer(offer_id, &offer);
+                true
+            }
+        }
+    }
+}
+
+impl Contract {
+    //sell a token that the market just received into a standing offer. If an offer ID is passed in, that offer is used.
+    //Otherwise the highest matching offer is used. Returns true if the token should be returned to the seller
+    pub(crate) fn internal_sell_into_standing_offer(
+        &mut self,
+        offer_id: Option<StandingOfferId>,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        seller_id: AccountId,
+    ) -> PromiseOrValue<bool> {
+        //find the offer to sell into
+        let offer_id = match offer_id {
+            Some(offer_id) => Some(offer_id),
+            None => self.internal_best_standing_offer(&nft_contract_id, &token_id, &seller_id),
+        };
+        let offer = offer_id.and_then(|offer_id| self.standing_offers.get(&offer_id));
+
+        //if no offer matches the token, the token goes back to the seller
+        let (offer_id, offer) = match (offer_id, offer) {
+            (Some(offer_id), Some(offer))
+                if offer.matches(&nft_contract_id, &token_id) && offer.buyer_id != seller_id =>
+            {
+                (offer_id, offer)
+            }
+            _ => {
+                log!("No standing offer matches token {}", token_id);
+                return PromiseOrValue::Value(true);
+            }
+        };
+
+        //remove the offer so it can't be used twice
+        self.internal_remove_standing_offer(offer_id);
+
+        //the market owns the token so it can transfer it to the buyer and get the payout from the nft contract
+        ext_contract::ext(nft_contract_id)
+            .with_attached_deposit(ONE_YOCTONEAR)
+            .with_static_gas(GAS_FOR_OFFER_TRANSFER_PAYOUT)
+            .nft_transfer_payout(
+                offer.buyer_id.clone(),           //buyer (person to transfer the NFT to)
+                token_id.clone(),                 //token ID to transfer
+                0, //the market owns the token so the approval ID isn't checked
+                "payout from market".to_string(), //memo (to include some context)
+                offer.amount,                     //the price the token was sold for
+                10, //the maximum amount of accounts the market can payout at once (this is limited by GAS)
+            )
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_RESOLVE_OFFER_SALE)
+                    .resolve_standing_offer_sale(seller_id, offer_id, offer, token_id),
+            )
+            .into()
+    }
+
+    //find the highest standing offer that can buy the given token. Ties go to the oldest offer
+    pub(crate) fn internal_best_standing_offer(
+        &self,
+        nft_contract_id: &AccountId,
+        token_id: &TokenId,
+        seller_id: &AccountId,
+    ) -> Option<StandingOfferId> {
+        self.standing_offers_by_nft_contract_id
+            .get(nft_contract_id)?
+            .iter()
+            .filter_map(|offer_id| self.standing_offers.get(&offer_id).map(|offer| (offer_id, offer)))
+            .filter(|(_, offer)| offer.matches(nft_contract_id, token_id) && &offer.buyer_id != seller_id)
+            .max_by(|a, b| a.1.amount.cmp(&b.1.amount).then_with(|| b.0.cmp(&a.0)))
+            .map(|(offer_id, _)| offer_id)
+    }
+
+    //if the nft contract has the most standing offers it can, remove the lowest one and refund it so a higher offer
+    //can take its place. Ties go to the oldest offer so the newest of the lowest offers is removed
+    pub(crate) fn internal_evict_lowest_standing_offer(
+        &mut self,
+        nft_contract_id: &AccountId,
+        amount: NearToken,
+    ) {
+        let by_nft_contract_id = match self.standing_offers_by_nft_contract_id.get(nft_contract_id) {
+            Some(by_nft_contract_id) if by_nft_contract_id.len() >= MAX_STANDING_OFFERS_PER_NFT_CONTRACT => {
+                by_nft_contract_id
+            }
+            _ => return,
+        };
+
+        let (lowest_offer_id, lowest_offer) = by_nft_contract_id
+            .iter()
+            .filter_map(|offer_id| self.standing_offers.get(&offer_id).map(|offer| (offer_id, offer)))
+            .min_by(|a, b| a.1.amount.cmp(&b.1.amount).then_with(|| b.0.cmp(&a.0)))
+            .expect("No standing offer by nft_contract_id");
+        assert!(
+            amount.gt(&lowest_offer.amount),
+            "The nft contract has {} standing offers. The offer must be more than the lowest one of {}",
+            MAX_STANDING_OFFERS_PER_NFT_CONTRACT,
+            lowest_offer.amount
+        );
+
+        //remove the lowest offer and refund its escrow
+        log!("Standing offer {} was outbid and refunded", lowest_offer_id);
+        let lowest_offer = self.internal_remove_standing_offer(lowest_offer_id);
+        Promise::new(lowest_offer.buyer_id).transfer(lowest_offer.amount);
+    }
+
+    //insert a standing offer and add it to the offers for its nft contract
+    pub(crate) fn internal_insert_standing_offer(
+        &mut self,
+        offer_id: StandingOfferId,
+        offer: &StandingOffer,
+    ) {
+        self.standing_offers.insert(&offer_id, offer);
+
+        //get the offers for the nft contract. If there are none, we create a new empty set
+        let mut by_nft_contract_id = self
+            .standing_offers_by_nft_contract_id
+            .get(&offer.nft_contract_id)
+            .unwrap_or_else(|| {
+                UnorderedSet::new(StorageKey::StandingOffersByNFTContractIdInner {
+                    //we get a new unique prefix for the collection by hashing the nft contract
+                    account_id_hash: hash_account_id(&offer.nft_contract_id),
+                })
+            });
+        by_nft_contract_id.insert(&offer_id);
+        self.standing_offers_by_nft_contract_id
+            .insert(&offer.nft_contract_id, &by_nft_contract_id);
+    }
+
+    //internal method for removing a standing offer. This returns the removed offer
+    pub(crate) fn internal_remove_standing_offer(&mut self, offer_id: StandingOfferId) -> StandingOffer {
+        let offer = self.standing_offers.remove(&offer_id).expect("No standing offer");
+
+        //remove the offer from the offers for its nft contract
+        let mut by_nft_contract_id = self
+            .standing_offers_by_nft_contract_id
+            .get(&offer.nft_contract_id)
+            .expect("No standing offer by nft_contract_id");
+        by_nft_contract_id.remove(&offer_id);
+
+        //if the set is now empty, we remove the nft contract from the map. Otherwise we insert the set back
+        if by_nft_contract_id.is_empty() {
+            self.standing_offers_by_nft_contract_id
+                .remove(&offer.nft_contract_id);
+        } else {
+            self.standing_offers_by_nft_contract_id
+                .insert(&offer.nft_contract_id, &by_nft_contract_id);
+        }
+
+        offer
+    }
+}
@@ -0,0 +1,64 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// views
+
+    //get the information for a given standing offer ID
+    pub fn get_standing_offer(&self, offer_id: StandingOfferId) -> Option<JsonStandingOffer> {
+        self.standing_offers
+            .get(&offer_id)
+            .map(|offer| JsonStandingOffer { offer_id, offer })
+    }
+
+    //returns the highest standing offer that would buy the given token if it was sent to the market
+    pub fn get_best_standing_offer(
+        &self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        seller_id: AccountId,
+    ) -> Option<JsonStandingOffer> {
+        self.internal_best_standing_offer(&nft_contract_id, &token_id, &seller_id)
+            .and_then(|offer_id| self.get_standing_offer(offer_id))
+    }
+
+    //returns the number of standing offers for a given contract (result is a string)
+    pub fn get_supply_standing_offers_by_nft_contract_id(&self, nft_contract_id: AccountId) -> U64 {
+        //if there was some set, return its length. Otherwise return 0
+        U64(self
+            .standing_offers_by_nft_contract_id
+            .get(&nft_contract_id)
+            .map(|offers| offers.len())
+            .unwrap_or(0))
+    }
+
+    //returns paginated standing offers for a given contract (result is a vector of offers)
+    pub fn get_standing_offers_by_nft_contract_id(
+        &self,
+        nft_contract_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<JsonStandingOffer> {
+        //get the set of offer IDs for the given contract. If there wasn't one, we return an empty vector
+        let offers = if let Some(offers) = self.standing_offers_by_nft_contract_id.get(&nft_contract_id) {
+            offers
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the offer IDs
+        offers
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            //we'll map the offer IDs into Json offers
+            .map(|offer_id| self.get_standing_offer(offer_id).unwrap())
+            //since we turned the keys into an iterator, we need to turn it back into a vector to return
+            .collect()
+    }
+}
@@ -0,0 +1,511 @@
+use crate::*;
+use near_sdk::{is_promise_success, log, NearSchema, PromiseResult};
+
+//the maximum number of tokens (on both sides combined) that can be traded in one swap. This is limited by GAS
+const MAX_TOKENS_PER_SWAP: usize = 4;
+//GAS for looking up the owner of an offered token
+const GAS_FOR_NFT_TOKEN: Gas = Gas::from_tgas(10);
+//GAS for the callback that checks the offered tokens on top of the GAS needed to move every token into the market
+const GAS_FOR_PROCESS_SWAP: Gas = Gas::from_tgas(10);
+//GAS for the swap callback on top of the GAS needed to hand out every token
+const GAS_FOR_RESOLVE_SWAP: Gas = Gas::from_tgas(10);
+//GAS for the callback that checks whether a token was handed out
+const GAS_FOR_RESOLVE_SWAP_HANDOUT: Gas = Gas::from_tgas(5);
+
+pub type SwapId = u64;
+
+//a token that is part of a swap
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapToken {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //actual token ID
+    pub token_id: TokenId,
+    //market contract's approval ID to transfer the token on behalf of the owner. This is None until the token is approved
+    pub approval_id: Option<u64>,
+}
+
+//struct that holds all the information about a proposed swap
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapProposal {
+    //account that proposed the swap
+    pub proposer_id: AccountId,
+    //account that owns the requested tokens
+    pub counterparty_id: AccountId,
+    //tokens the proposer gives up. These have been approved to the market
+    pub offered_tokens: Vec<SwapToken>,
+    //tokens the proposer wants in return. The counterparty accepts by approving each of them to the market and the
+    //swap can be executed once all of them are approved
+    pub requested_tokens: Vec<SwapToken>,
+    //NEAR the proposer adds to the trade. This is held in escrow until the swap is executed or cancelled
+    pub near_amount: NearToken,
+    //deposit the proposer paid to cover the storage of the proposal. This is refunded when the proposal is closed
+    pub storage_deposit: NearToken,
+}
+
+//The Json swap is what will be returned from view calls.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonSwap {
+    //unique ID of the swap
+    pub swap_id: SwapId,
+    //the swap proposal
+    pub proposal: SwapProposal,
+}
+
+//message the counterparty passes to nft_approve in order to accept a swap
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SwapApprovalMsg {
+    pub swap_id: SwapId,
+}
+
+#[near_bindgen]
+impl Contract {
+    //propose a swap of your tokens (approved to the market) plus optional NEAR for tokens owned by the counterparty.
+    //The attached deposit must cover the NEAR being offered plus the storage for the proposal.
+    #[payable]
+    pub fn propose_swap(
+        &mut self,
+        counterparty_id: AccountId,
+        offered_tokens: Vec<SwapToken>,
+        requested_tokens: Vec<SwapToken>,
+        near_amount: Option<NearToken>,
+    ) -> SwapId {
+        let proposer_id = env::predecessor_account_id();
+        assert_ne!(proposer_id, counterparty_id, "Cannot swap with yourself");
+
+        //make sure there is something to trade on both sides and that the swap fits in the GAS limits
+        assert!(
+            !offered_tokens.is_empty() || near_amount.map_or(false, |amount| !amount.is_zero()),
+            "Must offer tokens or NEAR"
+        );
+        assert!(!requested_tokens.is_empty(), "Must request at least one token");
+        assert!(
+            offered_tokens.len() + requested_tokens.len() <= MAX_TOKENS_PER_SWAP,
+            "Cannot swap more than {} tokens at once",
+            MAX_TOKENS_PER_SWAP
+        );
+        //the proposer's tokens must already be approved to the market
+        assert!(
+            offered_tokens.iter().all(|token| token.approval_id.is_some()),
+            "Offered tokens must include the market's approval ID"
+        );
+
+        //the NEAR being offered is held in escrow and the rest of the deposit pays for storage
+        let near_amount = near_amount.unwrap_or(ZERO_NEAR);
+        let storage_deposit = env::attached_deposit().saturating_sub(near_amount);
+
+        //get a new unique swap ID
+        let swap_id = self.next_swap_id;
+        self.next_swap_id += 1;
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //insert the swap and add it to the open swaps of both accounts. The requested tokens are measured as
+        //approved so the storage covers the approval IDs the counterparty adds when accepting
+        let mut proposal = SwapProposal {
+            proposer_id: proposer_id.clone(),
+            counterparty_id: counterparty_id.clone(),
+            offered_tokens,
+            requested_tokens: requested_tokens
+                .into_iter()
+                .map(|token| SwapToken {
+                    approval_id: Some(0),
+                    ..token
+                })
+                .collect(),
+            near_amount,
+            storage_deposit,
+        };
+        self.swaps.insert(&swap_id, &proposal);
+        self.internal_add_swap_to_account(&proposer_id, swap_id);
+        self.internal_add_swap_to_account(&counterparty_id, swap_id);
+
+        let required_cost = env::storage_byte_cost().saturating_mul(
+            env::storage_usage()
+                .saturating_sub(initial_storage_usage)
+                .into(),
+        );
+        assert!(
+            storage_deposit.ge(&required_cost),
+            "Must attach {} on top of the NEAR offered to cover storage",
+            required_cost
+        );
+
+        //the counterparty's approvals are only known once they approve the tokens to the market
+        for token in proposal.requested_tokens.iter_mut() {
+            token.approval_id = None;
+        }
+        self.swaps.insert(&swap_id, &proposal);
+
+        swap_id
+    }
+
+    //cancel a swap. The proposer can withdraw it and the counterparty can reject it.
+    //The escrowed NEAR and the storage deposit are refunded to the proposer
+    #[payable]
+    pub fn cancel_swap(&mut self, swap_id: SwapId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //remove the swap. If it doesn't exist, panic.
+        let swap = self.internal_remove_swap(swap_id);
+
+        //make sure the caller is part of the swap. If this fails, the removal will revert
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == swap.proposer_id || caller_id == swap.counterparty_id,
+            "Must be the proposer or counterparty of the swap"
+        );
+
+        //refund the proposer
+        Promise::new(swap.proposer_id.clone())
+            .transfer(swap.near_amount.saturating_add(swap.storage_deposit));
+    }
+
+    //execute a swap once the counterparty has approved every requested token. Either side of the swap can call this.
+    //The owner of every offered token is checked with its nft contract before anything is moved
+    pub fn execute_swap(&mut self, swap_id: SwapId) -> Promise {
+        let swap = self.swaps.get(&swap_id).expect("No swap");
+
+        //make sure the caller is part of the swap and the counterparty has accepted it
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == swap.proposer_id || caller_id == swap.counterparty_id,
+            "Must be the proposer or counterparty of the swap"
+        );
+        assert!(
+            swap.requested_tokens
+                .iter()
+                .all(|token| token.approval_id.is_some()),
+            "Every requested token must be approved to the market"
+        );
+
+        //make sure there's enough GAS to check, move and hand out every token. If not, the swap stays open
+        let gas_for_process = gas_for_process_swap(swap.offered_tokens.len() + swap.requested_tokens.len());
+        let required_gas = GAS_FOR_NFT_TOKEN
+            .saturating_mul(swap.offered_tokens.len() as u64)
+            .saturating_add(gas_for_process);
+        assert!(
+            env::prepaid_gas().saturating_sub(env::used_gas()) > required_gas,
+            "Executing this swap requires more than {} of GAS",
+            required_gas
+        );
+
+        //the swap is closed so it can't be executed twice
+        self.internal_remove_swap(swap_id);
+
+        //look up the owner of every offered token. The market is approved for every token listed on it, so an
+        //approval alone doesn't prove that the proposer owns the token they're offering
+        let lookups = swap
+            .offered_tokens
+            .iter()
+            .map(|token| {
+                ext_contract::ext(token.nft_contract_id.clone())
+                    .with_static_gas(GAS_FOR_NFT_TOKEN)
+                    .nft_token(token.token_id.clone())
+            })
+            .reduce(|joined, lookup| joined.and(lookup));
+
+        match lookups {
+            Some(lookups) => lookups.then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(gas_for_process)
+                    .process_swap(swap_id, swap),
+            ),
+            //the proposer only offered NEAR so there is nothing to check
+            None => self.internal_execute_swap(swap_id, swap),
+        }
+    }
+
+    /*
+        private method used to resolve the lookups of the offered tokens. If the proposer owns every offered token and
+        the market's approval is still current, every token is moved into the market's custody. Otherwise the proposer
+        is refunded and nothing is moved. Once a token changes hands its approvals are cleared, so a token can't be
+        taken from someone else between the lookup and the transfer.
+    */
+    #[private]
+    pub fn process_swap(&mut self, swap_id: SwapId, swap: SwapProposal) -> PromiseOrValue<bool> {
+        let market_id = env::current_account_id();
+        let owns_offered_tokens = swap.offered_tokens.iter().enumerate().all(|(index, token)| {
+            let nft_token = match env::promise_result(index as u64) {
+                PromiseResult::Successful(value) => {
+                    near_sdk::serde_json::from_slice::<Option<JsonToken>>(&value).ok().flatten()
+                }
+                _ => None,
+            };
+            nft_token.map_or(false, |nft_token| {
+                nft_token.owner_id == swap.proposer_id
+                    && nft_token.approved_account_ids.get(&market_id) == token.approval_id.as_ref()
+            })
+        });
+
+        if !owns_offered_tokens {
+            //refund the proposer for everything they escrowed
+            log!("Swap {} failed: the proposer doesn't own every offered token", swap_id);
+            Promise::new(swap.proposer_id.clone())
+                .transfer(swap.near_amount.saturating_add(swap.storage_deposit));
+            return PromiseOrValue::Value(false);
+        }
+
+        PromiseOrValue::Promise(self.internal_execute_swap(swap_id, swap))
+    }
+
+    /*
+        private method used to resolve the promises that moved every token of a swap into the market's custody.
+        If every token arrived, the tokens are handed out to their new owners and the NEAR is paid to the counterparty.
+        If any transfer failed, the trade is unwound: every token the market received goes back to its owner and the
+        proposer is refunded. Returns whether the swap was executed.
+    */
+    #[private]
+    pub fn resolve_swap(&mut self, swap_id: SwapId, swap: SwapProposal) -> bool {
+        //every leg of the swap along with who owned the token before and who should get it afterwards
+        let legs: Vec<(&SwapToken, &AccountId, &AccountId)> = swap
+            .offered_tokens
+            .iter()
+            .map(|token| (token, &swap.proposer_id, &swap.counterparty_id))
+            .chain(
+                swap.requested_tokens
+                    .iter()
+                    .map(|token| (token, &swap.counterparty_id, &swap.proposer_id)),
+            )
+            .collect();
+
+        //check which tokens made it into the market's custody
+        let received: Vec<bool> = (0..legs.len())
+            .map(|index| {
+                matches!(
+                    env::promise_result(index as u64),
+                    PromiseResult::Successful(_)
+                )
+            })
+            .collect();
+        let executed = received.iter().all(|received| *received);
+
+        //hand the tokens the market received to their new owners, or back to their old owners if the swap failed
+        for ((token, previous_owner_id, new_owner_id), received) in legs.iter().zip(received) {
+            if !received {
+                continue;
+            }
+            let receiver_id = if executed {
+                *new_owner_id
+            } else {
+                *previous_owner_id
+            };
+            internal_hand_out_swap_token(
+                token.nft_contract_id.clone(),
+                token.token_id.clone(),
+                (*receiver_id).clone(),
+            );
+        }
+
+        if executed {
+            //pay the counterparty the NEAR that was offered and refund the storage deposit to the proposer
+            if swap.near_amount.gt(&ZERO_NEAR) {
+                Promise::new(swap.counterparty_id.clone()).transfer(swap.near_amount);
+            }
+            Promise::new(swap.proposer_id.clone()).transfer(swap.storage_deposit);
+        } else {
+            //refund the proposer for everything they escrowed
+            env::log_str(&format!("Swap {} failed and was unwound", swap_id));
+            Promise::new(swap.proposer_id.clone())
+                .transfer(swap.near_amount.saturating_add(swap.storage_deposit));
+        }
+
+        executed
+    }
+
+    //private method used to resolve the promise when handing out a token after a swap. If the transfer failed,
+    //the market still holds the token so the receiver can claim it later
+    #[private]
+    pub fn resolve_swap_handout(
+        &mut self,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        receiver_id: AccountId,
+    ) {
+        if !is_promise_success() {
+            log!(
+                "Handing out token {} to {} failed. It can be claimed with claim_swap_token",
+                token_id,
+                receiver_id
+            );
+            self.swap_claims
+                .insert(&(nft_contract_id, token_id), &receiver_id);
+        }
+    }
+
+    //claim a token that the market couldn't hand out after a swap
+    #[payable]
+    pub fn claim_swap_token(&mut self, nft_contract_id: AccountId, token_id: TokenId) -> Promise {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //remove the claim and make sure the caller is the account that should get the token
+        let receiver_id = self
+            .swap_claims
+            .remove(&(nft_contract_id.clone(), token_id.clone()))
+            .expect("No swap claim");
+        assert_eq!(
+            env::predecessor_account_id(),
+            receiver_id,
+            "Must be the receiver of the token"
+        );
+
+        internal_hand_out_swap_token(nft_contract_id, token_id, receiver_id)
+    }
+}
+
+//the GAS the callback that checks the offered tokens needs to move every token into the market and resolve the swap
+fn gas_for_process_swap(token_count: usize) -> Gas {
+    GAS_FOR_PROCESS_SWAP
+        .saturating_add(GAS_FOR_NFT_TRANSFER.saturating_mul(token_count as u64))
+        .saturating_add(gas_for_resolve_swap(token_count))
+}
+
+//the GAS the swap callback needs to hand out every token
+fn gas_for_resolve_swap(token_count: usize) -> Gas {
+    GAS_FOR_RESOLVE_SWAP.saturating_add(
+        GAS_FOR_NFT_TRANSFER
+            .saturating_add(GAS_FOR_RESOLVE_SWAP_HANDOUT)
+            .saturating_mul(token_count as u64),
+    )
+}
+
+//transfer a token held by the market after a swap to its receiver. If the transfer fails, the receiver can claim it later
+fn internal_hand_out_swap_token(
+    nft_contract_id: AccountId,
+    token_id: TokenId,
+    receiver_id: AccountId,
+) -> Promise {
+    ext_contract::ext(nft_contract_id.clone())
+        .with_attached_deposit(ONE_YOCTONEAR)
+        .with_static_gas(GAS_FOR_NFT_TRANSFER)
+        .nft_transfer(
+            receiver_id.clone(),
+            token_id.clone(),
+            None,
+            Some("swap".to_string()),
+        )
+        .then(
+            Contract::ext(env::current_account_id())
+                .with_static_gas(GAS_FOR_RESOLVE_SWAP_HANDOUT)
+                .resolve_swap_handout(nft_contract_id, token_id, receiver_id),
+        )
+}
+
+impl Contract {
+    //record the counterparty's approval for one of the requested tokens. Once every requested token is approved,
+    //the swap can be executed with execute_swap
+    pub(crate) fn internal_accept_swap_token(
+        &mut self,
+        swap_id: SwapId,
+        nft_contract_id: AccountId,
+        token_id: TokenId,
+        owner_id: AccountId,
+        approval_id: u64,
+    ) {
+        //get the swap. If it doesn't exist, panic.
+        let mut swap = self.swaps.get(&swap_id).expect("No swap");
+        assert_eq!(
+            owner_id, swap.counterparty_id,
+            "Only the counterparty can accept a swap"
+        );
+
+        //find the requested token and record the market's approval ID for it
+        let requested_token = swap
+            .requested_tokens
+            .iter_mut()
+            .find(|token| token.nft_contract_id == nft_contract_id && token.token_id == token_id)
+            .expect("Token is not requested by the swap");
+        requested_token.approval_id = Some(approval_id);
+
+        self.swaps.insert(&swap_id, &swap);
+
+        //executing the swap takes more GAS than nft_on_approve is given, so it's done in its own call
+        if swap
+            .requested_tokens
+            .iter()
+            .all(|token| token.approval_id.is_some())
+        {
+            log!("Swap {} is accepted and can be executed", swap_id);
+        }
+    }
+
+    //move every token of the swap into the market's custody and resolve the swap once all the transfers are done
+    pub(crate) fn internal_execute_swap(&mut self, swap_id: SwapId, swap: SwapProposal) -> Promise {
+        let tokens: Vec<&SwapToken> = swap
+            .offered_tokens
+            .iter()
+            .chain(swap.requested_tokens.iter())
+            .collect();
+
+        //initiate a transfer into the market for every token and join them together
+        let transfers = tokens
+            .iter()
+            .map(|token| {
+                ext_contract::ext(token.nft_contract_id.clone())
+                    .with_attached_deposit(ONE_YOCTONEAR)
+                    .with_static_gas(GAS_FOR_NFT_TRANSFER)
+                    .nft_transfer(
+                        env::current_account_id(),
+                        token.token_id.clone(),
+                        token.approval_id,
+                        Some(format!("swap {}", swap_id)),
+                    )
+            })
+            .reduce(|joined, transfer| joined.and(transfer))
+            .expect("Swap has no tokens");
+
+        //the callback has to hand every token back out so it needs GAS for each transfer
+        transfers.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(gas_for_resolve_swap(tokens.len()))
+                .resolve_swap(swap_id, swap),
+        )
+    }
+
+    //add a swap to the set of open swaps for an account
+    pub(crate) fn internal_add_swap_to_account(&mut self, account_id: &AccountId, swap_id: SwapId) {
+        //get the swaps for the account. If there are none, we create a new empty set
+        let mut swaps = self.swaps_by_account.get(account_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::SwapsByAccountInner {
+                //we get a new unique prefix for the collection by hashing the account
+                account_id_hash: hash_account_id(account_id),
+            })
+        });
+
+        //insert the swap ID into the set and the set back into the map
+        swaps.insert(&swap_id);
+        self.swaps_by_account.insert(account_id, &swaps);
+    }
+
+    //remove a swap from the set of open swaps for an account
+    pub(crate) fn internal_remove_swap_from_account(&mut self, account_id: &AccountId, swap_id: SwapId) {
+        //get the swaps for the account. If there are none, there is nothing to remove
+        if let Some(mut swaps) = self.swaps_by_account.get(account_id) {
+            swaps.remove(&swap_id);
+
+            //if the set is now empty, we remove the account from the map. Otherwise we insert the set back
+            if swaps.is_empty() {
+                self.swaps_by_account.remove(account_id);
+            } else {
+                self.swaps_by_account.insert(account_id, &swaps);
+            }
+        }
+    }
+
+    //internal method for removing a swap. This returns the removed swap
+    pub(crate) fn internal_remove_swap(&mut self, swap_id: SwapId) -> SwapProposal {
+        let swap = self.swaps.remove(&swap_id).expect("No swap");
+        self.internal_remove_swap_from_account(&swap.proposer_id, swap_id);
+        self.internal_remove_swap_from_account(&swap.counterparty_id, swap_id);
+        swap
+    }
+}
@@ -0,0 +1,58 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    /// views
+
+    //get the information for a given swap ID
+    pub fn get_swap(&self, swap_id: SwapId) -> Option<JsonSwap> {
+        self.swaps
+            .get(&swap_id)
+            .map(|proposal| JsonSwap { swap_id, proposal })
+    }
+
+    //get the account that can claim a token the market couldn't hand out after a swap
+    pub fn get_swap_claim(&self, nft_contract_id: AccountId, token_id: TokenId) -> Option<AccountId> {
+        self.swap_claims.get(&(nft_contract_id, token_id))
+    }
+
+    //returns the number of open swaps for a given account, both as proposer and counterparty (result is a string)
+    pub fn get_supply_swaps_by_account(&self, account_id: AccountId) -> U64 {
+        //if there was some set, return its length. Otherwise return 0
+        U64(self
+            .swaps_by_account
+            .get(&account_id)
+            .map(|swaps| swaps.len())
+            .unwrap_or(0))
+    }
+
+    //returns paginated open swaps for a given account, both as proposer and counterparty (result is a vector of swaps)
+    pub fn get_swaps_by_account(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u32>,
+    ) -> Vec<JsonSwap> {
+        //get the set of swap IDs for the given account. If there wasn't one, we return an empty vector
+        let swaps = if let Some(swaps) = self.swaps_by_account.get(&account_id) {
+            swaps
+        } else {
+            return vec![];
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the swap IDs
+        swaps
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            //we'll map the swap IDs into Json swaps
+            .map(|swap_id| self.get_swap(swap_id).unwrap())
+            //since we turned the keys into an iterator, we need to turn it back into a vector to return
+            .collect()
+    }
+}
@@ -1,15 +1,22 @@
 /* unit tests */
-use crate::sale::Sale;
+use crate::auction::{hash_bid, vickrey_outcome, SealedBid, SealedBidAuction, MAX_BIDDERS_PER_AUCTION};
+use crate::buy_many::BuyManyItem;
+use crate::layaway::{forfeit_share, LayawayTerms};
+use crate::migrate::{OldContract, OldSale};
+use crate::sale::{JsonToken, Sale};
+use crate::standing_offer::MAX_STANDING_OFFERS_PER_NFT_CONTRACT;
+use crate::swap::{SwapProposal, SwapToken};
 #[cfg(test)]
-use crate::Contract;
+use crate::{Contract, StorageKey};
 use near_sdk::{
-    collections::UnorderedSet,
-    json_types::U128,
+    collections::{LookupMap, UnorderedMap, UnorderedSet},
+    json_types::{Base64VecU8, U128, U64},
     env,
     NearToken,
     test_utils::{accounts, VMContextBuilder},
-    testing_env, AccountId,
+    testing_env, AccountId, PromiseError, PromiseOrValue, PromiseResult,
 };
+use std::collections::HashMap;
 
 const MIN_REQUIRED_APPROVAL_YOCTO: NearToken = NearToken::from_yoctonear(170000000000000000000);
 const MIN_REQUIRED_STORAGE_YOCTO: NearToken =  NearToken::from_millinear(100);
@@ -123,11 +130,12 @@ fn test_remove_sale() {
         nft_contract_id: env::predecessor_account_id().to_string(), //NFT contract the token was minted on
         token_id: token_id.clone(),                                 //the actual token ID
         sale_conditions: NearToken::from_yoctonear(100), //the sale conditions -- price in YOCTO NEAR
+        in_custody: false,
     };
     let nft_contract_id = env::predecessor_account_id();
-    let contract_and_token_id = format!("{}{}{}", nft_contract_id, ".", token_id);
+    let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
     contract.sales.insert(&contract_and_token_id, &sale);
-    let owner_token_set = UnorderedSet::new(contract_and_token_id.as_bytes());
+    let owner_token_set = UnorderedSet::new(b"by_owner_id".as_slice());
     contract
         .by_owner_id
         .insert(&sale.owner_id, &owner_token_set);
@@ -174,11 +182,12 @@ fn test_update_price() {
         nft_contract_id: env::predecessor_account_id().to_string(), //NFT contract the token was minted on
         token_id: token_id.clone(),                                 //the actual token ID
         sale_conditions: nft_bid_yocto, //the sale conditions -- price in YOCTO NEAR
+        in_custody: false,
     };
     let nft_contract_id = env::predecessor_account_id();
-    let contract_and_token_id = format!("{}{}{}", nft_contract_id, ".", token_id);
+    let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
     contract.sales.insert(&contract_and_token_id, &sale);
-    let owner_token_set = UnorderedSet::new(contract_and_token_id.as_bytes());
+    let owner_token_set = UnorderedSet::new(b"by_owner_id".as_slice());
     contract
         .by_owner_id
         .insert(&sale.owner_id, &owner_token_set);
@@ -200,4 +209,1359 @@ fn test_update_price() {
     // test update price success
     let sale = contract.sales.get(&contract_and_token_id).expect("No sale");
     assert_eq!(sale.sale_conditions, new_price);
-}
\ No newline at end of file
+}
+
+fn sealed_bid(revealed_bid: Option<u128>) -> SealedBid {
+    SealedBid {
+        commitment: Base64VecU8(vec![0; 32]),
+        deposit: MIN_REQUIRED_STORAGE_YOCTO,
+        storage_deposit: NearToken::from_yoctonear(0),
+        revealed_bid: revealed_bid.map(NearToken::from_yoctonear),
+    }
+}
+
+#[test]
+fn test_vickrey_outcome_pays_second_price() {
+    let context = get_context(accounts(0));
+    testing_env!(context.build());
+
+    let mut bids = HashMap::new();
+    bids.insert(accounts(1), sealed_bid(Some(100)));
+    bids.insert(accounts(2), sealed_bid(Some(70)));
+    bids.insert(accounts(3), sealed_bid(None)); // never revealed
+
+    let outcome = vickrey_outcome(&bids, NearToken::from_yoctonear(10));
+    assert_eq!(outcome, Some((accounts(1), NearToken::from_yoctonear(70))));
+}
+
+#[test]
+fn test_vickrey_outcome_single_bid_pays_reserve() {
+    let context = get_context(accounts(0));
+    testing_env!(context.build());
+
+    let mut bids = HashMap::new();
+    bids.insert(accounts(1), sealed_bid(Some(100)));
+    bids.insert(accounts(2), sealed_bid(None));
+
+    let outcome = vickrey_outcome(&bids, NearToken::from_yoctonear(10));
+    assert_eq!(outcome, Some((accounts(1), NearToken::from_yoctonear(10))));
+
+    // nobody revealed, so there is no winner
+    bids.remove(&accounts(1));
+    assert_eq!(vickrey_outcome(&bids, NearToken::from_yoctonear(10)), None);
+}
+
+#[test]
+#[should_panic(expected = "Bid and salt do not match the commitment")]
+fn test_reveal_bid_wrong_salt() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // add auction with a commit phase ending at 1000ms and a reveal phase ending at 2000ms
+    let token_id = String::from("0n3C0ntr4ctT0Rul3Th3m4ll");
+    let nft_contract_id = env::predecessor_account_id();
+    let sale = Sale {
+        owner_id: accounts(0).clone(),
+        approval_id: 1,
+        nft_contract_id: nft_contract_id.to_string(),
+        token_id: token_id.clone(),
+        sale_conditions: NearToken::from_yoctonear(100), // reserve price
+        in_custody: false,
+    };
+    let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+    contract.sales.insert(&contract_and_token_id, &sale);
+    contract.auctions.insert(
+        &contract_and_token_id,
+        &SealedBidAuction {
+            commit_ends_at: 1000,
+            reveal_ends_at: 2000,
+        },
+    );
+
+    // commit a bid of 150 yoctoNEAR
+    let bid = NearToken::from_yoctonear(150);
+    testing_env!(context
+        .block_timestamp(500 * 1_000_000)
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.commit_bid(
+        nft_contract_id.clone(),
+        token_id.clone(),
+        Base64VecU8(hash_bid(bid, "salt")),
+    );
+
+    // reveal it with the wrong salt
+    testing_env!(context
+        .block_timestamp(1500 * 1_000_000)
+        .attached_deposit(NearToken::from_yoctonear(0))
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.reveal_bid(nft_contract_id, token_id, bid, "pepper".to_string());
+}
+
+#[test]
+#[should_panic(expected = "Auction cannot have more than 25 bidders")]
+fn test_commit_bid_to_full_auction() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // add auction with a commit phase ending at 1000ms and a reveal phase ending at 2000ms
+    let token_id = String::from("1");
+    let nft_contract_id = accounts(3);
+    let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+    contract.sales.insert(
+        &contract_and_token_id,
+        &Sale {
+            owner_id: accounts(0),
+            approval_id: 1,
+            nft_contract_id: nft_contract_id.to_string(),
+            token_id: token_id.clone(),
+            sale_conditions: NearToken::from_yoctonear(100), // reserve price
+            in_custody: false,
+        },
+    );
+    contract.auctions.insert(
+        &contract_and_token_id,
+        &SealedBidAuction {
+            commit_ends_at: 1000,
+            reveal_ends_at: 2000,
+        },
+    );
+
+    // fill the auction with bidders
+    let commitment = Base64VecU8(hash_bid(NearToken::from_yoctonear(150), "salt"));
+    for bidder in 0..MAX_BIDDERS_PER_AUCTION {
+        testing_env!(context
+            .block_timestamp(500 * 1_000_000)
+            .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+            .predecessor_account_id(format!("bidder{}.near", bidder).parse().unwrap())
+            .build());
+        contract.commit_bid(nft_contract_id.clone(), token_id.clone(), commitment.clone());
+    }
+
+    // a bidder that already committed can still top up their deposit
+    contract.commit_bid(nft_contract_id.clone(), token_id.clone(), commitment.clone());
+
+    // but a new bidder can't join
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.commit_bid(nft_contract_id, token_id, commitment);
+}
+
+#[test]
+fn test_commit_bid_pays_for_storage_separately() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // add auction with a reserve price of 100 yoctoNEAR
+    let token_id = String::from("1");
+    let nft_contract_id = accounts(3);
+    let contract_and_token_id = (nft_contract_id.clone(), token_id.clone());
+    contract.sales.insert(
+        &contract_and_token_id,
+        &Sale {
+            owner_id: accounts(0),
+            approval_id: 1,
+            nft_contract_id: nft_contract_id.to_string(),
+            token_id: token_id.clone(),
+            sale_conditions: NearToken::from_yoctonear(100), // reserve price
+            in_custody: false,
+        },
+    );
+    contract.auctions.insert(
+        &contract_and_token_id,
+        &SealedBidAuction {
+            commit_ends_at: 1000,
+            reveal_ends_at: 2000,
+        },
+    );
+
+    // the storage of the bid is taken from the attached deposit and the rest is escrowed
+    let commitment = Base64VecU8(hash_bid(NearToken::from_yoctonear(150), "salt"));
+    testing_env!(context
+        .block_timestamp(500 * 1_000_000)
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.commit_bid(nft_contract_id.clone(), token_id.clone(), commitment.clone());
+    let bid = contract.auction_bids.get(&contract_and_token_id).unwrap()[&accounts(1)].clone();
+    assert!(bid.storage_deposit.gt(&NearToken::from_yoctonear(0)));
+    assert_eq!(bid.deposit.saturating_add(bid.storage_deposit), MIN_REQUIRED_STORAGE_YOCTO);
+
+    // topping up adds everything to the escrow
+    contract.commit_bid(nft_contract_id, token_id, commitment);
+    let topped_up = contract.auction_bids.get(&contract_and_token_id).unwrap()[&accounts(1)].clone();
+    assert_eq!(topped_up.storage_deposit, bid.storage_deposit);
+    assert_eq!(topped_up.deposit, bid.deposit.saturating_add(MIN_REQUIRED_STORAGE_YOCTO));
+}
+
+#[test]
+#[should_panic(expected = "Token is already listed")]
+fn test_relist_auction_with_bids() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+
+    // list an auction and commit a bid to it
+    let token_id = String::from("1");
+    let nft_contract_id = accounts(3);
+    contract.internal_insert_sale(
+        Sale {
+            owner_id: accounts(1),
+            approval_id: 0,
+            nft_contract_id: nft_contract_id.to_string(),
+            token_id: token_id.clone(),
+            sale_conditions: NearToken::from_yoctonear(100), // reserve price
+            in_custody: false,
+        },
+        Some(SealedBidAuction {
+            commit_ends_at: 1000,
+            reveal_ends_at: 2000,
+        }),
+    );
+    testing_env!(context
+        .block_timestamp(500 * 1_000_000)
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.commit_bid(
+        nft_contract_id.clone(),
+        token_id.clone(),
+        Base64VecU8(hash_bid(NearToken::from_yoctonear(150), "salt")),
+    );
+
+    // the owner can't list the token again with a lower reserve price
+    testing_env!(context
+        .attached_deposit(NearToken::from_yoctonear(0))
+        .predecessor_account_id(env::current_account_id())
+        .build());
+    contract.process_listing(
+        accounts(1),
+        nft_contract_id,
+        token_id,
+        0,
+        NearToken::from_yoctonear(1),
+        None,
+        Ok(Some(json_token(accounts(1), Some(0)))),
+        Ok(true),
+    );
+}
+
+fn swap_token(token_id: &str, approval_id: Option<u64>) -> SwapToken {
+    SwapToken {
+        nft_contract_id: accounts(3),
+        token_id: token_id.to_string(),
+        approval_id,
+    }
+}
+
+#[test]
+fn test_propose_and_reject_swap() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // propose a swap of token 1 for token 2
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let swap_id = contract.propose_swap(
+        accounts(2),
+        vec![swap_token("1", Some(0))],
+        vec![swap_token("2", Some(7))],
+        None,
+    );
+
+    // the requested token's approval is only known once the counterparty approves it
+    let swap = contract.get_swap(swap_id).expect("No swap");
+    assert_eq!(swap.proposal.requested_tokens[0].approval_id, None);
+    assert_eq!(contract.get_supply_swaps_by_account(accounts(1)), U64(1));
+    assert_eq!(contract.get_swaps_by_account(accounts(2), None, None).len(), 1);
+
+    // the counterparty rejects the swap
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.cancel_swap(swap_id);
+    assert!(contract.get_swap(swap_id).is_none());
+    assert_eq!(contract.get_supply_swaps_by_account(accounts(1)), U64(0));
+    assert_eq!(contract.get_supply_swaps_by_account(accounts(2)), U64(0));
+}
+
+#[test]
+#[should_panic(expected = "on top of the NEAR offered to cover storage")]
+fn test_propose_swap_without_storage() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // only the NEAR offered is attached so nothing is left to pay for the proposal's storage
+    testing_env!(context
+        .attached_deposit(NearToken::from_millinear(1))
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.propose_swap(
+        accounts(2),
+        vec![],
+        vec![swap_token("2", None)],
+        Some(NearToken::from_millinear(1)),
+    );
+}
+
+#[test]
+fn test_accept_swap_token_waits_for_every_approval() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // propose a swap of token 1 for tokens 2 and 3
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let swap_id = contract.propose_swap(
+        accounts(2),
+        vec![swap_token("1", Some(0))],
+        vec![swap_token("2", None), swap_token("3", None)],
+        None,
+    );
+
+    // the counterparty approves the first requested token
+    testing_env!(context
+        .attached_deposit(NearToken::from_yoctonear(0))
+        .predecessor_account_id(accounts(3))
+        .build());
+    contract.internal_accept_swap_token(swap_id, accounts(3), "2".to_string(), accounts(2), 4);
+
+    // the swap stays open until the second requested token is approved too
+    let swap = contract.get_swap(swap_id).expect("No swap");
+    assert_eq!(swap.proposal.requested_tokens[0].approval_id, Some(4));
+    assert_eq!(swap.proposal.requested_tokens[1].approval_id, None);
+}
+
+#[test]
+#[should_panic(expected = "Only the counterparty can accept a swap")]
+fn test_accept_swap_token_wrong_owner() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let swap_id = contract.propose_swap(
+        accounts(2),
+        vec![swap_token("1", Some(0))],
+        vec![swap_token("2", None)],
+        None,
+    );
+
+    // someone other than the counterparty approves the requested token
+    contract.internal_accept_swap_token(swap_id, accounts(3), "2".to_string(), accounts(4), 0);
+}
+
+#[test]
+#[should_panic(expected = "Every requested token must be approved to the market")]
+fn test_execute_swap_before_accepted() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let swap_id = contract.propose_swap(
+        accounts(2),
+        vec![swap_token("1", Some(0))],
+        vec![swap_token("2", None)],
+        None,
+    );
+
+    // the proposer can't execute the swap before the counterparty approves the requested token
+    testing_env!(context
+        .attached_deposit(NearToken::from_yoctonear(0))
+        .build());
+    contract.execute_swap(swap_id);
+}
+
+fn offered_token_owner_result(owner_id: AccountId, approval_id: u64) -> PromiseResult {
+    let token = JsonToken {
+        owner_id,
+        approved_account_ids: HashMap::from([(env::current_account_id(), approval_id)]),
+    };
+    PromiseResult::Successful(near_sdk::serde_json::to_vec(&Some(token)).unwrap())
+}
+
+fn swap_proposal() -> SwapProposal {
+    SwapProposal {
+        proposer_id: accounts(1),
+        counterparty_id: accounts(2),
+        offered_tokens: vec![swap_token("1", Some(0))],
+        requested_tokens: vec![swap_token("2", Some(4))],
+        near_amount: NearToken::from_yoctonear(0),
+        storage_deposit: MIN_REQUIRED_STORAGE_YOCTO,
+    }
+}
+
+#[test]
+fn test_process_swap_offered_token_not_owned_by_proposer() {
+    let context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // the offered token is listed by someone else, so the market's approval for it is valid but the proposer isn't the owner
+    testing_env!(
+        context.build(),
+        near_sdk::test_vm_config(),
+        near_sdk::RuntimeFeesConfig::test(),
+        HashMap::default(),
+        vec![offered_token_owner_result(accounts(4), 0)]
+    );
+    let outcome = contract.process_swap(0, swap_proposal());
+    assert!(matches!(outcome, PromiseOrValue::Value(false)));
+}
+
+#[test]
+fn test_process_swap_stale_approval() {
+    let context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // the proposer owns the token but approved the market again since proposing the swap
+    testing_env!(
+        context.build(),
+        near_sdk::test_vm_config(),
+        near_sdk::RuntimeFeesConfig::test(),
+        HashMap::default(),
+        vec![offered_token_owner_result(accounts(1), 1)]
+    );
+    let outcome = contract.process_swap(0, swap_proposal());
+    assert!(matches!(outcome, PromiseOrValue::Value(false)));
+}
+
+#[test]
+fn test_process_swap_moves_tokens_owned_by_proposer() {
+    let context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(
+        context.build(),
+        near_sdk::test_vm_config(),
+        near_sdk::RuntimeFeesConfig::test(),
+        HashMap::default(),
+        vec![offered_token_owner_result(accounts(1), 0)]
+    );
+    let outcome = contract.process_swap(0, swap_proposal());
+    assert!(matches!(outcome, PromiseOrValue::Promise(_)));
+}
+
+#[test]
+fn test_best_standing_offer() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // a collection bid, a higher bid for token 1 only, and an even higher bid from the seller themselves
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let collection_offer_id = contract.make_standing_offer(accounts(3), None);
+    testing_env!(context
+        .attached_deposit(NearToken::from_millinear(200))
+        .predecessor_account_id(accounts(2))
+        .build());
+    let token_offer_id = contract.make_standing_offer(accounts(3), Some("1".to_string()));
+    testing_env!(context
+        .attached_deposit(NearToken::from_millinear(300))
+        .predecessor_account_id(accounts(4))
+        .build());
+    contract.make_standing_offer(accounts(3), None);
+    assert_eq!(
+        contract.get_supply_standing_offers_by_nft_contract_id(accounts(3)),
+        U64(3)
+    );
+
+    // the seller's own offer is never used, and the token offer only matches its token
+    let best = contract
+        .get_best_standing_offer(accounts(3), "1".to_string(), accounts(4))
+        .expect("No offer");
+    assert_eq!(best.offer_id, token_offer_id);
+    let best = contract
+        .get_best_standing_offer(accounts(3), "2".to_string(), accounts(4))
+        .expect("No offer");
+    assert_eq!(best.offer_id, collection_offer_id);
+    assert!(contract
+        .get_best_standing_offer(accounts(0), "1".to_string(), accounts(4))
+        .is_none());
+}
+
+#[test]
+fn test_full_standing_offers_evict_the_lowest() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // fill the nft contract with offers where the first one is the lowest
+    let mut offer_ids = Vec::new();
+    for offer in 0..MAX_STANDING_OFFERS_PER_NFT_CONTRACT {
+        testing_env!(context
+            .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO.saturating_add(NearToken::from_yoctonear(offer.into())))
+            .predecessor_account_id(accounts(1))
+            .build());
+        offer_ids.push(contract.make_standing_offer(accounts(3), None));
+    }
+
+    // a higher offer takes the place of the lowest one
+    testing_env!(context
+        .attached_deposit(NearToken::from_near(1))
+        .predecessor_account_id(accounts(2))
+        .build());
+    let offer_id = contract.make_standing_offer(accounts(3), None);
+    assert!(contract.get_standing_offer(offer_ids[0]).is_none());
+    assert!(contract.get_standing_offer(offer_ids[1]).is_some());
+    assert!(contract.get_standing_offer(offer_id).is_some());
+}
+
+#[test]
+#[should_panic(expected = "The offer must be more than the lowest one")]
+fn test_full_standing_offers_reject_a_lower_offer() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(NearToken::from_near(1))
+        .predecessor_account_id(accounts(1))
+        .build());
+    for _ in 0..MAX_STANDING_OFFERS_PER_NFT_CONTRACT {
+        contract.make_standing_offer(accounts(3), None);
+    }
+
+    // an offer that doesn't beat the lowest one can't be made
+    contract.make_standing_offer(accounts(3), None);
+}
+
+#[test]
+fn test_sell_into_standing_offer_without_match_returns_token() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let offer_id = contract.make_standing_offer(accounts(3), Some("1".to_string()));
+
+    // the offer is for a different token, so the token is returned and the offer stays open
+    let result = contract.internal_sell_into_standing_offer(
+        Some(offer_id),
+        accounts(3),
+        "2".to_string(),
+        accounts(2),
+    );
+    assert!(matches!(result, PromiseOrValue::Value(true)));
+    assert!(contract.get_standing_offer(offer_id).is_some());
+}
+
+#[test]
+#[should_panic(expected = "Must be the offer's buyer")]
+fn test_withdraw_standing_offer_wrong_account() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    let offer_id = contract.make_standing_offer(accounts(3), None);
+
+    // someone other than the buyer withdraws the offer
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.withdraw_standing_offer(offer_id);
+}
+
+#[test]
+#[should_panic(expected = "Standing offers must be at least")]
+fn test_standing_offer_below_storage_cost() {
+    let mut context = get_context(accounts(1));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // the offer doesn't cover the storage it uses while it's held
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.make_standing_offer(accounts(3), None);
+}
+
+#[test]
+fn test_list_and_remove_sale_in_custody() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // pay for storage for one sale
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+
+    // the token was transferred to the market with a listing msg
+    let token_id = String::from("0n3C0ntr4ctT0Rul3Th3m4ll");
+    contract.internal_list_in_custody(
+        accounts(1),
+        accounts(3),
+        token_id.clone(),
+        NearToken::from_yoctonear(100),
+    );
+    let sale = contract.get_sale(accounts(3), token_id.clone()).expect("No sale");
+    assert!(sale.in_custody);
+    assert_eq!(sale.approval_id, 0);
+    assert_eq!(contract.get_supply_by_owner_id(accounts(1)), U64(1));
+
+    // the owner takes the sale down and gets the token back
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.remove_sale(accounts(3), token_id.clone());
+    assert!(contract.get_sale(accounts(3), token_id).is_none());
+    assert_eq!(contract.get_supply_by_owner_id(accounts(1)), U64(0));
+}
+
+#[test]
+#[should_panic(expected = "Insufficient storage paid")]
+fn test_list_in_custody_without_storage() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // nothing was paid for storage so the token is given back
+    contract.internal_list_in_custody(
+        accounts(1),
+        accounts(3),
+        "1".to_string(),
+        NearToken::from_yoctonear(100),
+    );
+}
+
+#[test]
+fn test_sale_storage_is_charged_per_byte() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+
+    // a sale with a short token ID uses less storage than one with a long token ID
+    contract.internal_list_in_custody(accounts(1), accounts(3), "1".to_string(), NearToken::from_yoctonear(100));
+    let short_sale_bytes = contract.storage_used_of(accounts(1)).0;
+    assert!(short_sale_bytes > 0);
+    contract.internal_list_in_custody(
+        accounts(1),
+        accounts(3),
+        "1".repeat(64),
+        NearToken::from_yoctonear(100),
+    );
+    let long_sale_bytes = contract.storage_used_of(accounts(1)).0 - short_sale_bytes;
+    assert!(long_sale_bytes > short_sale_bytes);
+    assert_eq!(
+        contract.storage_available_of(accounts(1)),
+        MIN_REQUIRED_STORAGE_YOCTO.saturating_sub(
+            env::storage_byte_cost().saturating_mul((short_sale_bytes + long_sale_bytes).into())
+        )
+    );
+
+    // removing the sales releases the storage they were charged for
+    contract.internal_remove_sale(accounts(3), "1".repeat(64));
+    contract.internal_remove_sale(accounts(3), "1".to_string());
+    assert_eq!(contract.storage_used_of(accounts(1)), U64(0));
+    assert_eq!(contract.storage_available_of(accounts(1)), MIN_REQUIRED_STORAGE_YOCTO);
+}
+
+#[test]
+fn test_shared_sale_storage_is_not_charged_to_the_first_lister() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    for account_id in [accounts(1), accounts(2)] {
+        testing_env!(context
+            .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+            .predecessor_account_id(account_id)
+            .build());
+        contract.storage_deposit(None);
+    }
+
+    // both owners list a token on the same nft contract, so they share its set of token IDs
+    contract.internal_list_in_custody(accounts(1), accounts(3), "1".to_string(), NearToken::from_yoctonear(100));
+    contract.internal_list_in_custody(accounts(2), accounts(3), "2".to_string(), NearToken::from_yoctonear(100));
+
+    // the first lister gets all of their storage back even though the shared set is still in use
+    contract.internal_remove_sale(accounts(3), "1".to_string());
+    assert_eq!(contract.storage_used_of(accounts(1)), U64(0));
+
+    // the last owner to remove a sale only gets back what they were charged
+    let charged = contract.storage_used_of(accounts(2));
+    assert!(charged.0 > 0);
+    contract.internal_remove_sale(accounts(3), "2".to_string());
+    assert_eq!(contract.storage_used_of(accounts(2)), U64(0));
+}
+
+#[test]
+fn test_migrate_from_string_keys() {
+    let context = get_context(accounts(0));
+    testing_env!(context.build());
+
+    // write the state as it was before sales were keyed by (contract, token ID) pairs
+    let old_key = format!("{}.{}", accounts(3), "1");
+    let mut old_state = OldContract {
+        owner_id: accounts(0),
+        sales: UnorderedMap::new(StorageKey::Sales),
+        by_owner_id: LookupMap::new(StorageKey::ByOwnerId),
+        by_nft_contract_id: LookupMap::new(StorageKey::ByNFTContractId),
+        storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+    };
+    old_state.sales.insert(
+        &old_key,
+        &OldSale {
+            owner_id: accounts(1),
+            approval_id: 2,
+            nft_contract_id: accounts(3).to_string(),
+            token_id: "1".to_string(),
+            sale_conditions: NearToken::from_yoctonear(100),
+        },
+    );
+    let mut by_owner_id = UnorderedSet::new(StorageKey::ByOwnerIdInner {
+        account_id_hash: crate::hash_account_id(&accounts(1)),
+    });
+    by_owner_id.insert(&old_key);
+    old_state.by_owner_id.insert(&accounts(1), &by_owner_id);
+    let mut by_nft_contract_id = UnorderedSet::new(StorageKey::ByNFTContractIdInner {
+        account_id_hash: crate::hash_account_id(&accounts(3)),
+    });
+    by_nft_contract_id.insert(&"1".to_string());
+    old_state.by_nft_contract_id.insert(&accounts(3), &by_nft_contract_id);
+    old_state
+        .storage_deposits
+        .insert(&accounts(1), &MIN_REQUIRED_STORAGE_YOCTO);
+    env::state_write(&old_state);
+
+    // the sale is listed again under its new key and charged by the bytes it uses
+    let contract = Contract::migrate();
+    let sale = contract.get_sale(accounts(3), "1".to_string()).expect("No sale");
+    assert_eq!(sale.owner_id, accounts(1));
+    assert_eq!(sale.approval_id, 2);
+    assert!(!sale.in_custody);
+    assert_eq!(contract.get_supply_by_owner_id(accounts(1)), U64(1));
+    assert_eq!(contract.get_supply_by_nft_contract_id(accounts(3)), U64(1));
+    assert!(contract.storage_used_of(accounts(1)).0 > 0);
+    assert_eq!(contract.storage_balance_of(accounts(1)), MIN_REQUIRED_STORAGE_YOCTO);
+}
+
+#[test]
+fn test_sale_keys_do_not_collide() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+
+    // both pairs would have been keyed by "aa.near.b" if the parts were joined with a dot
+    let dotted_contract_id: AccountId = "aa.near".parse().unwrap();
+    let short_contract_id: AccountId = "aa".parse().unwrap();
+    contract.internal_list_in_custody(
+        accounts(1),
+        dotted_contract_id.clone(),
+        "b".to_string(),
+        NearToken::from_yoctonear(100),
+    );
+    contract.internal_list_in_custody(
+        accounts(1),
+        short_contract_id.clone(),
+        "near.b".to_string(),
+        NearToken::from_yoctonear(200),
+    );
+
+    assert_eq!(contract.get_supply_sales(), U64(2));
+    let sale = contract.get_sale(dotted_contract_id, "b".to_string()).expect("No sale");
+    assert_eq!(sale.sale_conditions, NearToken::from_yoctonear(100));
+    let sale = contract.get_sale(short_contract_id, "near.b".to_string()).expect("No sale");
+    assert_eq!(sale.sale_conditions, NearToken::from_yoctonear(200));
+}
+
+fn json_token(owner_id: AccountId, market_approval_id: Option<u64>) -> JsonToken {
+    JsonToken {
+        owner_id,
+        approved_account_ids: market_approval_id
+            .map(|approval_id| HashMap::from([(env::current_account_id(), approval_id)]))
+            .unwrap_or_default(),
+    }
+}
+
+// lists token "1" on accounts(3) for accounts(1) with approval ID 2 using the given callback results
+fn process_listing_with(
+    nft_token_result: Result<Option<JsonToken>, PromiseError>,
+    nft_is_approved_result: Result<bool, PromiseError>,
+) -> Contract {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+
+    contract.process_listing(
+        accounts(1),
+        accounts(3),
+        "1".to_string(),
+        2,
+        NearToken::from_yoctonear(100),
+        None,
+        nft_token_result,
+        nft_is_approved_result,
+    );
+    contract
+}
+
+#[test]
+fn test_process_listing() {
+    let contract = process_listing_with(Ok(Some(json_token(accounts(1), Some(2)))), Ok(true));
+    let sale = contract.get_sale(accounts(3), "1".to_string()).expect("No sale");
+    assert_eq!(sale.owner_id, accounts(1));
+    assert_eq!(sale.approval_id, 2);
+}
+
+#[test]
+#[should_panic(expected = "nft_token call failed")]
+fn test_process_listing_both_calls_failed() {
+    process_listing_with(Err(PromiseError::Failed), Err(PromiseError::Failed));
+}
+
+#[test]
+#[should_panic(expected = "nft_token call failed")]
+fn test_process_listing_nft_token_failed() {
+    process_listing_with(Err(PromiseError::Failed), Ok(true));
+}
+
+#[test]
+#[should_panic(expected = "nft_is_approved call failed")]
+fn test_process_listing_nft_is_approved_failed() {
+    process_listing_with(Ok(Some(json_token(accounts(1), Some(2)))), Err(PromiseError::Failed));
+}
+
+#[test]
+#[should_panic(expected = "Token does not exist")]
+fn test_process_listing_missing_token() {
+    process_listing_with(Ok(None), Ok(true));
+}
+
+#[test]
+#[should_panic(expected = "Token does not exist")]
+fn test_process_listing_missing_token_and_nft_is_approved_failed() {
+    process_listing_with(Ok(None), Err(PromiseError::Failed));
+}
+
+#[test]
+#[should_panic(expected = "Signer is not NFT owner")]
+fn test_process_listing_wrong_owner() {
+    process_listing_with(Ok(Some(json_token(accounts(2), Some(2)))), Ok(true));
+}
+
+#[test]
+#[should_panic(expected = "Marketplace contract is not approved")]
+fn test_process_listing_not_approved() {
+    process_listing_with(Ok(Some(json_token(accounts(1), None))), Ok(false));
+}
+
+#[test]
+#[should_panic(expected = "Approval ID 2 is not the marketplace's current approval")]
+fn test_process_listing_stale_approval_id() {
+    process_listing_with(Ok(Some(json_token(accounts(1), Some(3)))), Ok(true));
+}
+
+// lists token "1" on accounts(3) for rent by accounts(1) and rents it to accounts(2) for 2 days at day 0
+fn rented_token(context: &mut VMContextBuilder) -> Contract {
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+    contract.internal_list_for_rent(
+        accounts(1),
+        accounts(3),
+        "1".to_string(),
+        NearToken::from_millinear(10),
+        7,
+        NearToken::from_near(1),
+    );
+
+    testing_env!(context
+        .attached_deposit(NearToken::from_millinear(1020))
+        .predecessor_account_id(accounts(2))
+        .block_timestamp(0)
+        .build());
+    contract.rent(accounts(3), "1".to_string(), 2);
+    contract
+}
+
+#[test]
+fn test_rent_and_return_in_time() {
+    let mut context = get_context(accounts(0));
+    let mut contract = rented_token(&mut context);
+    let active = contract
+        .get_rental(accounts(3), "1".to_string())
+        .and_then(|rental| rental.active)
+        .expect("Token is not rented");
+    assert_eq!(active.renter_id, accounts(2));
+    assert_eq!(active.rent, NearToken::from_millinear(20));
+    assert_eq!(active.ends_at, 2 * 86_400_000);
+
+    // someone other than the renter can't return the token
+    assert!(contract.internal_return_rental(accounts(3), "1".to_string(), accounts(4)));
+
+    // the renter returns the token a day later and the token can be rented again
+    testing_env!(context.block_timestamp(86_400_000 * 1_000_000).build());
+    assert!(!contract.internal_return_rental(accounts(3), "1".to_string(), accounts(2)));
+    let rental = contract.get_rental(accounts(3), "1".to_string()).expect("No rental");
+    assert!(rental.active.is_none());
+}
+
+#[test]
+#[should_panic(expected = "The rental has not ended")]
+fn test_claim_rental_collateral_before_deadline() {
+    let mut context = get_context(accounts(0));
+    let mut contract = rented_token(&mut context);
+
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(86_400_000 * 1_000_000)
+        .build());
+    contract.claim_rental_collateral(accounts(3), "1".to_string());
+}
+
+#[test]
+fn test_claim_rental_collateral_after_deadline() {
+    let mut context = get_context(accounts(0));
+    let mut contract = rented_token(&mut context);
+
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(3 * 86_400_000 * 1_000_000)
+        .build());
+    contract.claim_rental_collateral(accounts(3), "1".to_string());
+    assert!(contract.get_rental(accounts(3), "1".to_string()).is_none());
+    assert_eq!(contract.storage_used_of(accounts(1)), U64(0));
+
+    // the renter keeps the token, so returning it now is rejected
+    assert!(contract.internal_return_rental(accounts(3), "1".to_string(), accounts(2)));
+}
+
+#[test]
+fn test_claim_rental_collateral_releases_only_the_rental_storage() {
+    let mut context = get_context(accounts(0));
+    let mut contract = rented_token(&mut context);
+    let rental_storage = contract.storage_used_of(accounts(1));
+
+    // the owner also lists another token
+    contract.internal_list_in_custody(accounts(1), accounts(3), "2".to_string(), NearToken::from_yoctonear(100));
+    let sale_storage = contract.storage_used_of(accounts(1)).0 - rental_storage.0;
+
+    // the rental is stored with its renter, but the owner only gets back what they were charged for it
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .block_timestamp(3 * 86_400_000 * 1_000_000)
+        .build());
+    contract.claim_rental_collateral(accounts(3), "1".to_string());
+    assert_eq!(contract.storage_used_of(accounts(1)), U64(sale_storage));
+}
+
+// lists token "1" on accounts(3) for 1 NEAR by accounts(1) with a 0.1 NEAR deposit and 3 daily installments,
+// then reserves it for accounts(2) at day 0
+fn reserved_sale(context: &mut VMContextBuilder) -> Contract {
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+    contract.internal_list_in_custody(accounts(1), accounts(3), "1".to_string(), NearToken::from_near(1));
+
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.set_layaway_terms(
+        accounts(3),
+        "1".to_string(),
+        LayawayTerms {
+            deposit: NearToken::from_millinear(100),
+            installments: 3,
+            installment_period_ms: 86_400_000,
+            forfeit_bps: 5_000,
+        },
+    );
+
+    testing_env!(context
+        .attached_deposit(NearToken::from_millinear(100))
+        .predecessor_account_id(accounts(2))
+        .block_timestamp(0)
+        .build());
+    contract.reserve(accounts(3), "1".to_string());
+    contract
+}
+
+#[test]
+#[should_panic(expected = "Installments cannot take longer than")]
+fn test_layaway_terms_that_overflow() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+    contract.internal_list_in_custody(accounts(1), accounts(3), "1".to_string(), NearToken::from_near(1));
+
+    // the deadline of a reservation wouldn't fit in a u64
+    testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+    contract.set_layaway_terms(
+        accounts(3),
+        "1".to_string(),
+        LayawayTerms {
+            deposit: NearToken::from_millinear(100),
+            installments: u32::MAX,
+            installment_period_ms: u64::MAX / 2,
+            forfeit_bps: 5_000,
+        },
+    );
+}
+
+#[test]
+fn test_layaway_amount_due() {
+    let mut context = get_context(accounts(0));
+    let mut contract = reserved_sale(&mut context);
+
+    // the deposit is due up front and each day adds a third of the remaining 0.9 NEAR
+    let layaway = contract.get_layaway(accounts(3), "1".to_string()).expect("No layaway");
+    assert_eq!(layaway.amount_due, Some(NearToken::from_millinear(100)));
+    assert_eq!(layaway.deadline, Some(3 * 86_400_000));
+
+    // the buyer pays the first installment on day 1
+    testing_env!(context
+        .attached_deposit(NearToken::from_millinear(300))
+        .predecessor_account_id(accounts(2))
+        .block_timestamp(86_400_000 * 1_000_000)
+        .build());
+    contract.pay_installment(accounts(3), "1".to_string());
+    let layaway = contract.get_layaway(accounts(3), "1".to_string()).expect("No layaway");
+    assert_eq!(layaway.amount_due, Some(NearToken::from_millinear(400)));
+    assert_eq!(
+        layaway.reservation.map(|reservation| reservation.paid),
+        Some(NearToken::from_millinear(400))
+    );
+}
+
+#[test]
+#[should_panic(expected = "Sale is reserved")]
+fn test_layaway_blocks_offer() {
+    let mut context = get_context(accounts(0));
+    let mut contract = reserved_sale(&mut context);
+
+    testing_env!(context
+        .attached_deposit(NearToken::from_near(1))
+        .predecessor_account_id(accounts(4))
+        .build());
+    contract.offer(accounts(3), "1".to_string());
+}
+
+#[test]
+fn test_layaway_default_reopens_sale() {
+    let mut context = get_context(accounts(0));
+    let mut contract = reserved_sale(&mut context);
+
+    // the buyer misses the first installment, which was due at the end of day 1, and anyone can release the reservation
+    testing_env!(context
+        .attached_deposit(NearToken::from_yoctonear(0))
+        .predecessor_account_id(accounts(4))
+        .block_timestamp((86_400_000 + 1) * 1_000_000)
+        .build());
+    contract.release_defaulted_layaway(accounts(3), "1".to_string());
+
+    // the terms stay up so the token can be reserved again
+    let layaway = contract.get_layaway(accounts(3), "1".to_string()).expect("No layaway");
+    assert!(layaway.reservation.is_none());
+    assert!(contract.get_sale(accounts(3), "1".to_string()).is_some());
+}
+
+#[test]
+#[should_panic(expected = "Reservation is in good standing")]
+fn test_layaway_release_in_good_standing() {
+    let mut context = get_context(accounts(0));
+    let mut contract = reserved_sale(&mut context);
+
+    contract.release_defaulted_layaway(accounts(3), "1".to_string());
+}
+
+#[test]
+#[should_panic(expected = "Insufficient storage paid")]
+fn test_reserve_without_seller_storage() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+    contract.internal_list_in_custody(accounts(1), accounts(3), "1".to_string(), NearToken::from_near(1));
+    testing_env!(context.attached_deposit(ONE_YOCTONEAR).build());
+    contract.set_layaway_terms(
+        accounts(3),
+        "1".to_string(),
+        LayawayTerms {
+            deposit: NearToken::from_millinear(100),
+            installments: 3,
+            installment_period_ms: 86_400_000,
+            forfeit_bps: 5_000,
+        },
+    );
+
+    // the seller has only paid for the storage they use so far
+    let used_bytes = contract.storage_used_of(accounts(1)).0;
+    contract
+        .storage_deposits
+        .insert(&accounts(1), &env::storage_byte_cost().saturating_mul(used_bytes.into()));
+
+    // the reservation is charged to the seller, so it can't be stored
+    testing_env!(context
+        .attached_deposit(NearToken::from_millinear(100))
+        .predecessor_account_id(accounts(2))
+        .build());
+    contract.reserve(accounts(3), "1".to_string());
+}
+
+#[test]
+#[should_panic(expected = "Token is already listed")]
+fn test_relist_reserved_sale() {
+    let mut context = get_context(accounts(0));
+    let mut contract = reserved_sale(&mut context);
+
+    // the seller can't list the reserved token again, which would replace the layaway terms
+    testing_env!(context
+        .attached_deposit(NearToken::from_yoctonear(0))
+        .predecessor_account_id(env::current_account_id())
+        .build());
+    contract.process_listing(
+        accounts(1),
+        accounts(3),
+        "1".to_string(),
+        0,
+        NearToken::from_millinear(500),
+        None,
+        Ok(Some(json_token(accounts(1), Some(0)))),
+        Ok(true),
+    );
+}
+
+#[test]
+fn test_forfeit_share_of_large_payment() {
+    let context = get_context(accounts(0));
+    testing_env!(context.build());
+
+    assert_eq!(
+        forfeit_share(NearToken::from_millinear(400), 5_000),
+        NearToken::from_millinear(200)
+    );
+    // the payment times the share doesn't fit in a u128
+    assert_eq!(
+        forfeit_share(NearToken::from_yoctonear(u128::MAX), 5_000),
+        NearToken::from_yoctonear(u128::MAX / 2)
+    );
+    assert_eq!(
+        forfeit_share(NearToken::from_yoctonear(u128::MAX), 10_000),
+        NearToken::from_yoctonear(u128::MAX)
+    );
+}
+
+fn buy_many_item(token_id: &str, max_price: NearToken) -> BuyManyItem {
+    BuyManyItem {
+        nft_contract_id: accounts(3),
+        token_id: token_id.to_string(),
+        max_price,
+    }
+}
+
+#[test]
+fn test_buy_many_skips_items_that_cannot_be_bought() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+    contract.internal_list_in_custody(accounts(1), accounts(3), "1".to_string(), NearToken::from_near(1));
+    contract.internal_list_in_custody(accounts(1), accounts(3), "2".to_string(), NearToken::from_near(2));
+
+    // token 1 is above the max price, token 2 is above what's left of the deposit and token 3 isn't for sale
+    testing_env!(context
+        .attached_deposit(NearToken::from_near(1))
+        .predecessor_account_id(accounts(2))
+        .build());
+    let result = contract.buy_many(vec![
+        buy_many_item("1", NearToken::from_millinear(500)),
+        buy_many_item("2", NearToken::from_near(2)),
+        buy_many_item("3", NearToken::from_near(1)),
+    ]);
+    let results = match result {
+        PromiseOrValue::Value(results) => results,
+        PromiseOrValue::Promise(_) => panic!("Nothing should have been bought"),
+    };
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|result| result.price.is_none()));
+
+    // every sale is still up
+    assert_eq!(contract.get_supply_sales(), U64(2));
+}
+
+#[test]
+fn test_buy_many_price_within_deposit() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.storage_deposit(None);
+    contract.internal_list_in_custody(accounts(1), accounts(3), "1".to_string(), NearToken::from_near(1));
+
+    let item = buy_many_item("1", NearToken::from_near(2));
+    // the listed price is used as long as there's enough deposit left
+    assert_eq!(
+        contract.internal_buy_many_price(&item, &accounts(2), NearToken::from_near(1)),
+        Some(NearToken::from_near(1))
+    );
+    assert_eq!(
+        contract.internal_buy_many_price(&item, &accounts(2), NearToken::from_millinear(999)),
+        None
+    );
+    // sellers can't buy their own sales
+    assert_eq!(
+        contract.internal_buy_many_price(&item, &accounts(1), NearToken::from_near(1)),
+        None
+    );
+}
+
+#[test]
+fn test_register_and_unregister_royalty_receiver() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // the deposit attached to the registration pays for its storage
+    testing_env!(context
+        .attached_deposit(MIN_REQUIRED_STORAGE_YOCTO)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.register_royalty_receiver();
+    assert!(contract.is_royalty_receiver(accounts(1)));
+    assert!(!contract.is_royalty_receiver(accounts(2)));
+    assert!(contract.storage_used_of(accounts(1)).0 > 0);
+    assert_eq!(contract.storage_balance_of(accounts(1)), MIN_REQUIRED_STORAGE_YOCTO);
+
+    // unregistering releases the storage so it can be withdrawn
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.unregister_royalty_receiver();
+    assert!(!contract.is_royalty_receiver(accounts(1)));
+    assert_eq!(contract.storage_used_of(accounts(1)), U64(0));
+}
+
+#[test]
+#[should_panic(expected = "Insufficient storage paid")]
+fn test_register_royalty_receiver_without_storage() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.register_royalty_receiver();
+}
+
+#[test]
+fn test_failed_royalty_notification_is_set_aside() {
+    let mut context = get_context(accounts(0));
+    testing_env!(context.build());
+    let mut contract = Contract::new(accounts(0));
+
+    // the market holds plenty of escrow, but the royalty is only paid out of the refund of the failed notification
+    testing_env!(
+        context.account_balance(NearToken::from_near(10)).build(),
+        near_sdk::test_vm_config(),
+        near_sdk::RuntimeFeesConfig::test(),
+        HashMap::default(),
+        vec![PromiseResult::Failed]
+    );
+    contract.resolve_royalty_notification(accounts(1), NearToken::from_near(1));
+    contract.resolve_royalty_notification(accounts(1), NearToken::from_near(2));
+    assert_eq!(contract.get_unpaid_royalties(accounts(1)), NearToken::from_near(3));
+
+    // the receiver claims what was set aside for them
+    testing_env!(context
+        .attached_deposit(ONE_YOCTONEAR)
+        .predecessor_account_id(accounts(1))
+        .build());
+    contract.claim_unpaid_royalties();
+    assert_eq!(contract.get_unpaid_royalties(accounts(1)), NearToken::from_yoctonear(0));
+}
@@ -1,9 +1,33 @@
 use crate::*;
 use near_sdk::ext_contract;
 
+//an account's approval to transfer a token
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct Approval {
+    //the approval ID that was given out when the account was approved
+    pub approval_id: u64,
+    //when the approval lapses, Unix epoch in milliseconds. If this is None, the approval lasts until it's revoked
+    pub expires_at: Option<u64>,
+}
+
+impl Approval {
+    //whether the approval has expired at the given time
+    pub(crate) fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.map_or(false, |expires_at| now >= expires_at)
+    }
+}
+
 pub trait NonFungibleTokenCore {
-    //approve an account ID to transfer a token on your behalf
-    fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>);
+    //approve an account ID to transfer a token on your behalf. The approval can optionally expire at a given time
+    fn nft_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        msg: Option<String>,
+        expires_at: Option<u64>,
+    );
 
     //check if the passed in account has access to approve the token ID
     fn nft_is_approved(
@@ -36,7 +60,13 @@ trait NonFungibleTokenApprovalsReceiver {
 impl NonFungibleTokenCore for Contract {
     //allow a specific account ID to approve a token on your behalf
     #[payable]
-    fn nft_approve(&mut self, token_id: TokenId, account_id: AccountId, msg: Option<String>) {
+    fn nft_approve(
+        &mut self,
+        token_id: TokenId,
+        account_id: AccountId,
+        msg: Option<String>,
+        expires_at: Option<u64>,
+    ) {
         /*
             assert at least one yocto for security reasons - this will cause a redirect to the NEAR wallet.
             The user needs to attach enough to pay for storage on the contract
@@ -53,24 +83,40 @@ impl NonFungibleTokenCore for Contract {
             "Predecessor must be the token owner."
         );
 
+        //soulbound tokens can't be transferred so nobody can be approved to transfer them
+        assert_transferable(&token);
+        //nobody can be approved while the token is locked
+        assert_not_locked(&token);
+        //if the validity policy is on, nobody can be approved for an expired token
+        self.assert_not_expired(&token_id);
+        //an approval that has already lapsed would be useless
+        if let Some(expires_at) = expires_at {
+            assert!(
+                expires_at > env::block_timestamp_ms(),
+                "The approval must expire in the future"
+            );
+        }
+
         //get the next approval ID if we need a new approval
         let approval_id: u64 = token.next_approval_id;
 
-        //check if the account has been approved already for this token
-        let is_new_approval = token
-            .approved_account_ids
-            //insert returns none if the key was not present.
-            .insert(account_id.clone(), approval_id)
-            //if the key was not present, .is_none() will return true so it is a new approval.
-            .is_none();
-
-        //if it was a new approval, we need to calculate how much storage is being used to add the account.
-        let storage_used = if is_new_approval {
-            bytes_for_approved_account_id(&account_id)
-        //if it was not a new approval, we used no storage.
-        } else {
-            0
+        //approve the account. If it was approved already, the old approval is replaced
+        let approval = Approval {
+            approval_id,
+            expires_at,
         };
+        let previous_approval = token
+            .approved_account_ids
+            .insert(account_id.clone(), approval.clone());
+
+        //calculate how much storage the approval uses. Replacing an approval only uses the difference, which
+        //is less than nothing if the old approval had an expiry and the new one doesn't
+        let previous_bytes = previous_approval
+            .as_ref()
+            .map_or(0, |previous_approval| bytes_for_approval(&account_id, previous_approval));
+        let approval_bytes = bytes_for_approval(&account_id, &approval);
+        let storage_used = approval_bytes.saturating_sub(previous_bytes);
+        let storage_released = previous_bytes.saturating_sub(approval_bytes);
 
         //increment the token's next approval ID by 1
         token.next_approval_id += 1;
@@ -79,6 +125,20 @@ impl NonFungibleTokenCore for Contract {
 
         //refund any excess storage attached by the user. If the user didn't attach enough, panic.
         refund_deposit(storage_used);
+        //refund the storage released by replacing an approval with a smaller one
+        if storage_released > 0 {
+            Promise::new(token.owner_id.clone())
+                .transfer(env::storage_byte_cost().saturating_mul(storage_released));
+        }
+
+        //log the approval so indexers know the account can transfer the token
+        log_approval_event(EventLogVariant::NftApprove(vec![NftApproveLog {
+            owner_id: token.owner_id.to_string(),
+            token_id: token_id.clone(),
+            account_id: account_id.to_string(),
+            approval_id,
+            expires_at,
+        }]));
 
         //if some message was passed into the function, we initiate a cross contract call on the
         //account we're giving access to.
@@ -100,15 +160,20 @@ impl NonFungibleTokenCore for Contract {
         //get the token object from the token_id
         let token = self.tokens_by_id.get(&token_id).expect("No token");
 
+        //operators are approved for all of the owner's tokens, whatever approval ID is passed in
+        if self.is_operator(&token.owner_id, &approved_account_id) {
+            return true;
+        }
+
         //get the approval number for the passed in account ID
         let approval = token.approved_account_ids.get(&approved_account_id);
 
-        //if there was some approval ID found for the account ID
-        if let Some(approval) = approval {
+        //if there was some approval found for the account ID. Expired approvals count as revoked
+        if let Some(approval) = approval.filter(|approval| !approval.is_expired(env::block_timestamp_ms())) {
             //if a specific approval_id was passed into the function
             if let Some(approval_id) = approval_id {
                 //return if the approval ID passed in matches the actual approval ID for the account
-                approval_id == *approval
+                approval_id == approval.approval_id
             //if there was no approval_id passed into the function, we simply return true
             } else {
                 true
@@ -132,12 +197,23 @@ impl NonFungibleTokenCore for Contract {
         assert_eq!(&predecessor_account_id, &token.owner_id);
 
         //if the account ID was in the token's approval, we remove it and the if statement logic executes
-        if token.approved_account_ids.remove(&account_id).is_some() {
+        if let Some(approval) = token.approved_account_ids.remove(&account_id) {
             //refund the funds released by removing the approved_account_id to the caller of the function
-            refund_approved_account_ids_iter(predecessor_account_id, [account_id].iter());
+            refund_approved_account_ids_iter(
+                predecessor_account_id.clone(),
+                [(&account_id, &approval)].into_iter(),
+            );
 
             //insert the token back into the tokens_by_id collection with the account_id removed from the approval list
             self.tokens_by_id.insert(&token_id, &token);
+
+            //log the revoked approval
+            log_approval_event(EventLogVariant::NftRevoke(vec![NftRevokeLog {
+                owner_id: predecessor_account_id.to_string(),
+                token_id,
+                account_id: account_id.to_string(),
+                approval_id: approval.approval_id,
+            }]));
         }
     }
 
@@ -156,11 +232,152 @@ impl NonFungibleTokenCore for Contract {
         //only revoke if the approved account IDs for the token is not empty
         if !token.approved_account_ids.is_empty() {
             //refund the approved account IDs to the caller of the function
-            refund_approved_account_ids(predecessor_account_id, &token.approved_account_ids);
-            //clear the approved account IDs
-            token.approved_account_ids.clear();
+            refund_approved_account_ids(predecessor_account_id.clone(), &token.approved_account_ids);
+            //clear the approved account IDs and keep them with their approval IDs for the log
+            let approved_account_ids = token
+                .approved_account_ids
+                .drain()
+                .map(|(account_id, approval)| (account_id.to_string(), approval.approval_id))
+                .collect();
             //insert the token back into the tokens_by_id collection with the approved account IDs cleared
             self.tokens_by_id.insert(&token_id, &token);
+
+            //log that every approval was revoked
+            log_approval_event(EventLogVariant::NftRevokeAll(vec![NftRevokeAllLog {
+                owner_id: predecessor_account_id.to_string(),
+                token_id,
+                approved_account_ids,
+            }]));
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //get when an account's approval for a token expires. Returns None if the approval doesn't expire or doesn't exist
+    pub fn nft_approval_expires_at(&self, token_id: TokenId, account_id: AccountId) -> Option<u64> {
+        self.tokens_by_id
+            .get(&token_id)
+            .expect("No token")
+            .approved_account_ids
+            .get(&account_id)
+            .and_then(|approval| approval.expires_at)
+    }
+
+    //remove the approvals for a token that have expired. Anyone can call this. The storage that is released
+    //is refunded to the owner of the token, who paid for it. Returns the accounts whose approvals were removed
+    pub fn nft_remove_expired_approvals(&mut self, token_id: TokenId) -> Vec<AccountId> {
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+
+        //find the approvals that have expired
+        let now = env::block_timestamp_ms();
+        let expired: Vec<(AccountId, Approval)> = token
+            .approved_account_ids
+            .iter()
+            .filter(|(_, approval)| approval.is_expired(now))
+            .map(|(account_id, approval)| (account_id.clone(), approval.clone()))
+            .collect();
+
+        //only update the token if something expired
+        if !expired.is_empty() {
+            let mut revoke_logs = Vec::new();
+            for (account_id, approval) in expired.iter() {
+                token.approved_account_ids.remove(account_id);
+                revoke_logs.push(NftRevokeLog {
+                    owner_id: token.owner_id.to_string(),
+                    token_id: token_id.clone(),
+                    account_id: account_id.to_string(),
+                    approval_id: approval.approval_id,
+                });
+            }
+            self.tokens_by_id.insert(&token_id, &token);
+
+            //refund the owner for the storage released by removing the approvals
+            refund_approved_account_ids_iter(
+                token.owner_id,
+                expired.iter().map(|(account_id, approval)| (account_id, approval)),
+            );
+
+            //log the approvals that were removed as revoked
+            log_approval_event(EventLogVariant::NftRevoke(revoke_logs));
+        }
+
+        expired.into_iter().map(|(account_id, _)| account_id).collect()
+    }
+}
+
+//log an approval or revocation as per the events standard
+fn log_approval_event(event: EventLogVariant) {
+    let approval_log: EventLog = EventLog {
+        // Standard name ("nep171").
+        standard: NFT_STANDARD_NAME.to_string(),
+        // Version of the standard ("nft-1.0.0").
+        version: NFT_METADATA_SPEC.to_string(),
+        // The data related with the event stored in a vector.
+        event,
+    };
+
+    // Log the serialized json.
+    env::log_str(&approval_log.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    //the storage an approval uses on the contract, measured by adding it to a minted token
+    fn measured_approval_bytes(contract: &mut Contract, approval: &Approval) -> u64 {
+        let initial_storage_usage = env::storage_usage();
+        let mut token = contract.tokens_by_id.get(&"1".to_string()).unwrap();
+        token.approved_account_ids.clear();
+        token.approved_account_ids.insert(accounts(2), approval.clone());
+        contract.tokens_by_id.insert(&"1".to_string(), &token);
+        let used = env::storage_usage() - initial_storage_usage;
+
+        //remove the approval again so the next one is measured on its own
+        token.approved_account_ids.clear();
+        contract.tokens_by_id.insert(&"1".to_string(), &token);
+        used
+    }
+
+    #[test]
+    fn approval_bytes_match_the_storage_used() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(1));
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(1));
+        contract.internal_mint(
+            "1".to_string(),
+            accounts(1),
+            TokenMetadata {
+                title: None,
+                description: None,
+                media: None,
+                media_hash: None,
+                copies: None,
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra: None,
+                reference: None,
+                reference_hash: None,
+            },
+            None,
+            true,
+        );
+
+        for expires_at in [None, Some(1_000)] {
+            let approval = Approval {
+                approval_id: 0,
+                expires_at,
+            };
+            assert_eq!(
+                bytes_for_approval(&accounts(2), &approval),
+                measured_approval_bytes(&mut contract, &approval) as u128
+            );
         }
     }
 }
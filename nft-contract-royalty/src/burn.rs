@@ -0,0 +1,86 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //burn a token. This can be called by the owner or an approved account. The storage that is released
+    //is refunded to the owner of the token
+    #[payable]
+    pub fn nft_burn(&mut self, token_id: TokenId, approval_id: Option<u64>, memo: Option<String>) {
+        //assert that the user attached exactly 1 yoctoNEAR. This is for security and so that the user will be redirected to the NEAR wallet.
+        assert_one_yocto();
+        //get the account that is burning the token
+        let sender_id = env::predecessor_account_id();
+
+        //get the token object by passing in the token_id
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+
+        //make sure the sender is the owner or an approved account
+        assert_authorized(&token, &sender_id, approval_id);
+        //locked tokens can't be burned until they're unlocked or the lock ends
+        assert_not_locked(&token);
+
+        //if the sender isn't the owner, they're logged as the authorized account
+        let authorized_id = if sender_id != token.owner_id {
+            Some(sender_id.to_string())
+        } else {
+            None
+        };
+
+        //remove the token and refund the owner
+        self.internal_burn(&token_id, authorized_id, memo);
+    }
+}
+
+impl Contract {
+    //removes the token from the contract, refunds the released storage to the owner and logs the burn (internal method and can't be called directly via CLI).
+    pub(crate) fn internal_burn(
+        &mut self,
+        token_id: &TokenId,
+        authorized_id: Option<String>,
+        memo: Option<String>,
+    ) -> Token {
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //remove the token and its metadata. If the token doesn't exist, panic
+        let token = self.tokens_by_id.remove(token_id).expect("No token");
+        self.token_metadata_by_id.remove(token_id);
+
+        //we remove the token from it's owner's set
+        self.internal_remove_token_from_owner(&token.owner_id, token_id);
+
+        //calculate the storage that was released (this includes the storage used by the approved account IDs)
+        let released_storage_in_bytes = initial_storage_usage.saturating_sub(env::storage_usage());
+
+        //refund the owner for the storage that was released
+        let refund = env::storage_byte_cost().saturating_mul(released_storage_in_bytes.into());
+        if refund.gt(&ONE_YOCTONEAR) {
+            Promise::new(token.owner_id.clone()).transfer(refund);
+        }
+
+        // Construct the burn log as per the events standard.
+        let nft_burn_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // Vector of token IDs that were burned.
+                token_ids: vec![token_id.to_string()],
+                // The optional authorized account ID that burned the token on behalf of the owner.
+                authorized_id,
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_burn_log.to_string());
+
+        //return the token that was burned
+        token
+    }
+}
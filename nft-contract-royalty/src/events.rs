@@ -1,9 +1,12 @@
+use std::collections::HashMap;
 use std::fmt;
 
 use near_sdk::serde::{Deserialize, Serialize};
 
 /// Enum that represents the data type of the EventLog.
-/// The enum can either be an NftMint or an NftTransfer.
+/// The enum can either be an NftMint, an NftTransfer, an NftBurn, an NftMetadataUpdate, a ContractMetadataUpdate,
+/// an NftLock, an NftUnlock, an NftRoyaltyUpdate, an NftApproveAll, an NftRevokeOperator, an NftApprove,
+/// an NftRevoke or an NftRevokeAll.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +15,17 @@ use near_sdk::serde::{Deserialize, Serialize};
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
     NftTransfer(Vec<NftTransferLog>),
+    NftBurn(Vec<NftBurnLog>),
+    NftMetadataUpdate(Vec<NftMetadataUpdateLog>),
+    ContractMetadataUpdate(Vec<ContractMetadataUpdateLog>),
+    NftLock(Vec<NftLockLog>),
+    NftUnlock(Vec<NftUnlockLog>),
+    NftRoyaltyUpdate(Vec<NftRoyaltyUpdateLog>),
+    NftApproveAll(Vec<NftOperatorLog>),
+    NftRevokeOperator(Vec<NftOperatorLog>),
+    NftApprove(Vec<NftApproveLog>),
+    NftRevoke(Vec<NftRevokeLog>),
+    NftRevokeAll(Vec<NftRevokeAllLog>),
 }
 
 /// Interface to capture data about an event
@@ -78,6 +92,169 @@ pub struct NftTransferLog {
     pub memo: Option<String>,
 }
 
+/// An event log to capture token burning
+///
+/// Arguments
+/// * `owner_id`: owner of the tokens that were burned
+/// * `token_ids`: ["1", "abc"]
+/// * `authorized_id`: approved account that burned the tokens
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftBurnLog {
+    pub owner_id: String,
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub authorized_id: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture token metadata updates
+///
+/// Arguments
+/// * `token_ids`: ["1", "abc"]
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftMetadataUpdateLog {
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture contract metadata updates
+///
+/// Arguments
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ContractMetadataUpdateLog {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture tokens being locked
+///
+/// Arguments
+/// * `owner_id`: owner of the tokens that were locked
+/// * `locked_by`: account the tokens are locked to
+/// * `token_ids`: ["1", "abc"]
+/// * `until`: optional time the lock ends, Unix epoch in milliseconds
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftLockLog {
+    pub owner_id: String,
+    pub locked_by: String,
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture tokens being unlocked
+///
+/// Arguments
+/// * `owner_id`: owner of the tokens that were unlocked
+/// * `locked_by`: account the tokens were locked to
+/// * `token_ids`: ["1", "abc"]
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftUnlockLog {
+    pub owner_id: String,
+    pub locked_by: String,
+    pub token_ids: Vec<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture updates to the perpetual royalties of tokens
+///
+/// Arguments
+/// * `token_ids`: ["1", "abc"]
+/// * `royalty`: the new royalties in basis points, keyed by receiver
+/// * `memo`: optional message
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRoyaltyUpdateLog {
+    pub token_ids: Vec<String>,
+    pub royalty: HashMap<String, u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo: Option<String>,
+}
+
+/// An event log to capture an owner approving or revoking an operator for all of their tokens
+///
+/// Arguments
+/// * `owner_id`: owner of the tokens
+/// * `operator_id`: account that was approved or revoked
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftOperatorLog {
+    pub owner_id: String,
+    pub operator_id: String,
+}
+
+/// An event log to capture an account being approved to transfer a token
+///
+/// Arguments
+/// * `owner_id`: owner of the token
+/// * `token_id`: "1"
+/// * `account_id`: account that was approved
+/// * `approval_id`: the approval ID that was given out
+/// * `expires_at`: optional time the approval lapses, Unix epoch in milliseconds
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftApproveLog {
+    pub owner_id: String,
+    pub token_id: String,
+    pub account_id: String,
+    pub approval_id: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+}
+
+/// An event log to capture an account's approval for a token being revoked
+///
+/// Arguments
+/// * `owner_id`: owner of the token
+/// * `token_id`: "1"
+/// * `account_id`: account that was revoked
+/// * `approval_id`: the approval ID that was revoked
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRevokeLog {
+    pub owner_id: String,
+    pub token_id: String,
+    pub account_id: String,
+    pub approval_id: u64,
+}
+
+/// An event log to capture every approval for a token being revoked
+///
+/// Arguments
+/// * `owner_id`: owner of the token
+/// * `token_id`: "1"
+/// * `approved_account_ids`: the approval IDs that were revoked, keyed by account
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftRevokeAllLog {
+    pub owner_id: String,
+    pub token_id: String,
+    pub approved_account_ids: HashMap<String, u64>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +312,171 @@ mod tests {
         };
         assert_eq!(expected, log.to_string());
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn nep_format_burn() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_burn","data":[{"owner_id":"user1.near","token_ids":["token"],"authorized_id":"market.near","memo":"Burn!"}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftBurn(vec![NftBurnLog {
+                owner_id: "user1.near".to_string(),
+                token_ids: vec!["token".to_string()],
+                authorized_id: Some("market.near".to_string()),
+                memo: Some("Burn!".to_owned()),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_metadata_update() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"nft_metadata_update","data":[{"token_ids":["1","2"]}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.1.0".to_string(),
+            event: EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+                token_ids: vec!["1".to_string(), "2".to_string()],
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_contract_metadata_update() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.1.0","event":"contract_metadata_update","data":[{"memo":"Rebrand"}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.1.0".to_string(),
+            event: EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog {
+                memo: Some("Rebrand".to_string()),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_lock() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_lock","data":[{"owner_id":"user1.near","locked_by":"staking.near","token_ids":["token"],"until":1700000000000}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftLock(vec![NftLockLog {
+                owner_id: "user1.near".to_string(),
+                locked_by: "staking.near".to_string(),
+                token_ids: vec!["token".to_string()],
+                until: Some(1700000000000),
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_unlock() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_unlock","data":[{"owner_id":"user1.near","locked_by":"staking.near","token_ids":["token"]}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftUnlock(vec![NftUnlockLog {
+                owner_id: "user1.near".to_string(),
+                locked_by: "staking.near".to_string(),
+                token_ids: vec!["token".to_string()],
+                memo: None,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_royalty_update() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_royalty_update","data":[{"token_ids":["token"],"royalty":{"artist.near":500},"memo":"Lower royalty"}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftRoyaltyUpdate(vec![NftRoyaltyUpdateLog {
+                token_ids: vec!["token".to_string()],
+                royalty: HashMap::from([("artist.near".to_string(), 500)]),
+                memo: Some("Lower royalty".to_string()),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_approve_all() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_approve_all","data":[{"owner_id":"user1.near","operator_id":"market.near"}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftApproveAll(vec![NftOperatorLog {
+                owner_id: "user1.near".to_string(),
+                operator_id: "market.near".to_string(),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_revoke_operator() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_revoke_operator","data":[{"owner_id":"user1.near","operator_id":"market.near"}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftRevokeOperator(vec![NftOperatorLog {
+                owner_id: "user1.near".to_string(),
+                operator_id: "market.near".to_string(),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_approve() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_approve","data":[{"owner_id":"user1.near","token_id":"token","account_id":"market.near","approval_id":3,"expires_at":1700000000000}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftApprove(vec![NftApproveLog {
+                owner_id: "user1.near".to_string(),
+                token_id: "token".to_string(),
+                account_id: "market.near".to_string(),
+                approval_id: 3,
+                expires_at: Some(1700000000000),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_revoke() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_revoke","data":[{"owner_id":"user1.near","token_id":"token","account_id":"market.near","approval_id":3}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftRevoke(vec![NftRevokeLog {
+                owner_id: "user1.near".to_string(),
+                token_id: "token".to_string(),
+                account_id: "market.near".to_string(),
+                approval_id: 3,
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+
+    #[test]
+    fn nep_format_revoke_all() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_revoke_all","data":[{"owner_id":"user1.near","token_id":"token","approved_account_ids":{"market.near":3}}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftRevokeAll(vec![NftRevokeAllLog {
+                owner_id: "user1.near".to_string(),
+                token_id: "token".to_string(),
+                approved_account_ids: HashMap::from([("market.near".to_string(), 3)]),
+            }]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
+}
@@ -1,6 +1,5 @@
 use crate::*;
 use near_sdk::CryptoHash;
-use std::mem::size_of;
 
 //convert the royalty percentage and amount to pay into a payout
 pub(crate) fn royalty_to_payout(royalty_percentage: u128, amount_to_pay: NearToken) -> NearToken {
@@ -9,23 +8,30 @@ pub(crate) fn royalty_to_payout(royalty_percentage: u128, amount_to_pay: NearTok
         .saturating_div(10000)
 }
 
-//calculate how many bytes the account ID is taking up
-pub(crate) fn bytes_for_approved_account_id(account_id: &AccountId) -> u128 {
+//calculate how many bytes an approval is taking up
+pub(crate) fn bytes_for_approval(account_id: &AccountId, approval: &Approval) -> u128 {
     // The extra 4 bytes are coming from Borsh serialization to store the length of the string.
-    account_id.as_str().len() as u128 + 4 + size_of::<u128>() as u128
+    // The approval takes up 8 bytes for its ID plus 1 byte for the expiry, or 9 if it has an expiry.
+    /*
+        approvals used to be charged 16 bytes for their ID whether they could expire or not. Those approvals
+        never expire, so they're refunded 9 bytes and the refund is never more than what the owner paid.
+    */
+    account_id.as_str().len() as u128
+        + 4
+        + near_sdk::borsh::object_length(approval).unwrap_or(0) as u128
 }
 
-//refund the storage taken up by passed in approved account IDs and send the funds to the passed in account ID.
+//refund the storage taken up by passed in approvals and send the funds to the passed in account ID.
 pub(crate) fn refund_approved_account_ids_iter<'a, I>(
     account_id: AccountId,
-    approved_account_ids: I, //the approved account IDs must be passed in as an iterator
+    approved_account_ids: I, //the approvals must be passed in as an iterator
 ) -> Promise
 where
-    I: Iterator<Item = &'a AccountId>,
+    I: Iterator<Item = (&'a AccountId, &'a Approval)>,
 {
-    //get the storage total by going through and summing all the bytes for each approved account IDs
+    //get the storage total by going through and summing all the bytes for each approval
     let storage_released = approved_account_ids
-        .map(bytes_for_approved_account_id)
+        .map(|(account_id, approval)| bytes_for_approval(account_id, approval))
         .sum();
     //transfer the account the storage that is released
     let amount_to_be_released = env::storage_byte_cost().saturating_mul(storage_released);
@@ -35,10 +41,10 @@ where
 //refund a map of approved account IDs and send the funds to the passed in account ID
 pub(crate) fn refund_approved_account_ids(
     account_id: AccountId,
-    approved_account_ids: &HashMap<AccountId, u64>,
+    approved_account_ids: &HashMap<AccountId, Approval>,
 ) -> Promise {
-    //call the refund_approved_account_ids_iter with the approved account IDs as keys
-    refund_approved_account_ids_iter(account_id, approved_account_ids.keys())
+    //call the refund_approved_account_ids_iter with every approval in the map
+    refund_approved_account_ids_iter(account_id, approved_account_ids.iter())
 }
 
 //used to generate a unique prefix in our storage collections (this is to avoid data collisions)
@@ -90,6 +96,57 @@ pub(crate) fn refund_deposit(storage_used: u128) {
     }
 }
 
+//make sure the sender is the owner of the token or an approved account. If an approval ID is passed in,
+//it has to be the sender's current approval ID
+pub(crate) fn assert_authorized(token: &Token, sender_id: &AccountId, approval_id: Option<u64>) {
+    //if the sender doesn't equal the owner, we check if the sender is in the approval list
+    if sender_id != &token.owner_id {
+        //if the token's approved account IDs doesn't contain the sender or their approval expired, we panic
+        let approval = match token.approved_account_ids.get(sender_id) {
+            Some(approval) if !approval.is_expired(env::block_timestamp_ms()) => approval,
+            _ => env::panic_str("Unauthorized"),
+        };
+
+        // If they included an approval_id, check if the sender's actual approval_id is the same as the one included
+        if let Some(enforced_approval_id) = approval_id {
+            //get the actual approval ID
+            let actual_approval_id = approval.approval_id;
+
+            //make sure that the actual approval ID is the same as the one provided
+            assert_eq!(
+                actual_approval_id, enforced_approval_id,
+                "The actual approval_id {} is different from the given approval_id {}",
+                actual_approval_id, enforced_approval_id,
+            );
+        }
+    }
+}
+
+//charge or refund the caller for the change in storage since the initial storage usage was measured
+pub(crate) fn refund_storage_difference(initial_storage_usage: u64) {
+    let storage_usage = env::storage_usage();
+    if storage_usage > initial_storage_usage {
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit((storage_usage - initial_storage_usage).into());
+    } else {
+        //refund the released storage along with the attached deposit
+        let released_storage = env::storage_byte_cost()
+            .saturating_mul((initial_storage_usage - storage_usage).into());
+        let refund = released_storage.saturating_add(env::attached_deposit());
+        if refund.gt(&ONE_YOCTONEAR) {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+    }
+}
+
+//make sure the token isn't soulbound
+pub(crate) fn assert_transferable(token: &Token) {
+    assert!(
+        token.transferable,
+        "Token is soulbound and cannot be transferred"
+    );
+}
+
 impl Contract {
     //add a token to the set of tokens an owner has
     pub(crate) fn internal_add_token_to_owner(
@@ -151,31 +208,42 @@ impl Contract {
         //get the token object by passing in the token_id
         let token = self.tokens_by_id.get(token_id).expect("No token");
 
-        //if the sender doesn't equal the owner, we check if the sender is in the approval list
-        if sender_id != &token.owner_id {
-            //if the token's approved account IDs doesn't contain the sender, we panic
-            if !token.approved_account_ids.contains_key(sender_id) {
-                env::panic_str("Unauthorized");
-            }
-
-            // If they included an approval_id, check if the sender's actual approval_id is the same as the one included
-            if let Some(enforced_approval_id) = approval_id {
-                //get the actual approval ID
-                let actual_approval_id = token
-                    .approved_account_ids
-                    .get(sender_id)
-                    //if the sender isn't in the map, we panic
-                    .expect("Sender is not approved account");
-
-                //make sure that the actual approval ID is the same as the one provided
-                assert_eq!(
-                    actual_approval_id, &enforced_approval_id,
-                    "The actual approval_id {} is different from the given approval_id {}",
-                    actual_approval_id, enforced_approval_id,
-                );
-            }
+        //soulbound tokens can't be transferred. This is checked first since nobody can be approved for them
+        assert_transferable(&token);
+
+        //locked tokens can't be transferred until they're unlocked or the lock ends
+        assert_not_locked(&token);
+        //if the validity policy is on, expired tokens can't be transferred
+        self.assert_not_expired(token_id);
+
+        //operators can transfer all of the owner's tokens. They aren't tied to an approval ID so it isn't checked
+        let is_operator = sender_id != &token.owner_id && self.is_operator(&token.owner_id, sender_id);
+        //otherwise make sure the sender is the owner or an approved account
+        if !is_operator {
+            assert_authorized(&token, sender_id, approval_id);
         }
 
+        // Default the authorized ID to be None for the logs.
+        let mut authorized_id = None;
+        //if the approval ID was provided or the sender is an operator, set the authorized ID equal to the sender
+        if approval_id.is_some() || is_operator {
+            authorized_id = Some(sender_id.to_string());
+        }
+
+        //move the token to the receiver and log the transfer
+        self.internal_move_token(token, receiver_id, token_id, authorized_id, memo)
+    }
+
+    //moves the token to the receiver_id and logs the transfer without checking who is moving it or whether
+    //the token is transferable (internal method and can't be called directly via CLI).
+    pub(crate) fn internal_move_token(
+        &mut self,
+        token: Token,
+        receiver_id: &AccountId,
+        token_id: &TokenId,
+        authorized_id: Option<String>,
+        memo: Option<String>,
+    ) -> Token {
         //we make sure that the sender isn't sending the token to themselves
         assert_ne!(
             &token.owner_id, receiver_id,
@@ -195,6 +263,14 @@ impl Contract {
             next_approval_id: token.next_approval_id,
             //we copy over the royalties from the previous token
             royalty: token.royalty.clone(),
+            //the creator and frozen metadata don't change when the token is transferred
+            creator_id: token.creator_id.clone(),
+            metadata_frozen: token.metadata_frozen,
+            transferable: token.transferable,
+            //a pending recovery is cancelled once the token moves
+            pending_recovery: None,
+            //a token can't move while it's locked, so any lock has ended
+            lock: None,
         };
         //insert that new token into the tokens_by_id, replacing the old entry
         self.tokens_by_id.insert(token_id, &new_token);
@@ -204,13 +280,6 @@ impl Contract {
             env::log_str(&format!("Memo: {}", memo).to_string());
         }
 
-        // Default the authorized ID to be None for the logs.
-        let mut authorized_id = None;
-        //if the approval ID was provided, set the authorized ID equal to the sender
-        if approval_id.is_some() {
-            authorized_id = Some(sender_id.to_string());
-        }
-
         // Construct the transfer log as per the events standard.
         let nft_transfer_log: EventLog = EventLog {
             // Standard name ("nep171").
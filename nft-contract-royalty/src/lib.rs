@@ -14,6 +14,9 @@ pub use crate::nft_core::*;
 pub use crate::approval::*;
 pub use crate::royalty::*;
 pub use crate::events::*;
+pub use crate::owner::*;
+pub use crate::metadata_update::*;
+pub use crate::lock::*;
 
 mod internal;
 mod enumeration; 
@@ -23,9 +26,19 @@ mod nft_core;
 mod approval; 
 mod royalty;
 mod events;
+mod burn;
+mod owner;
+mod metadata_update;
+mod soulbound;
+mod lock;
+mod validity;
+mod operator;
+mod migrate;
 
 /// This spec can be treated like a version of the standard.
 pub const NFT_METADATA_SPEC: &str = "1.0.0";
+/// This is the version of the standard that added the metadata update events.
+pub const NFT_METADATA_UPDATE_SPEC: &str = "1.1.0";
 /// This is the name of the NFT standard we're using
 pub const NFT_STANDARD_NAME: &str = "nep171";
 
@@ -50,6 +63,21 @@ pub struct Contract {
 
     //keeps track of the metadata for the contract
     pub metadata: LazyOption<NFTContractMetadata>,
+
+    //keeps track of the accounts that are approved to mint and how many more tokens they can mint (None means no quota)
+    pub minters: UnorderedMap<AccountId, Option<u32>>,
+
+    //whether anyone can mint or only the owner and approved minters
+    pub open_mint: bool,
+
+    //who is allowed to update the metadata of tokens
+    pub metadata_update_policy: MetadataUpdatePolicy,
+
+    //whether expired tokens can't be transferred or approved
+    pub enforce_validity: bool,
+
+    //keeps track of the operators that an owner approved to transfer all of their tokens
+    pub operators_per_owner: LookupMap<AccountId, UnorderedSet<AccountId>>,
 }
 
 /// Helper structure for keys of the persistent collections.
@@ -64,6 +92,9 @@ pub enum StorageKey {
     TokensPerType,
     TokensPerTypeInner { token_type_hash: CryptoHash },
     TokenTypesLocked,
+    Minters,
+    OperatorsPerOwner,
+    OperatorsPerOwnerInner { account_id_hash: CryptoHash },
 }
 
 #[near_bindgen]
@@ -97,6 +128,9 @@ impl Contract {
     */
     #[init]
     pub fn new(owner_id: AccountId, metadata: NFTContractMetadata) -> Self {
+        //make sure the metadata follows the metadata standard
+        metadata.assert_valid();
+
         //create a variable of type Self with all the fields initialized. 
         let this = Self {
             //Storage keys are simply the prefixes used for the collections. This helps avoid data collision
@@ -109,6 +143,15 @@ impl Contract {
                 StorageKey::NFTContractMetadata,
                 Some(&metadata),
             ),
+            //only the owner can mint until minters are added or minting is opened up
+            minters: UnorderedMap::new(StorageKey::Minters),
+            open_mint: false,
+            //token metadata can't be updated until the owner picks a policy
+            metadata_update_policy: MetadataUpdatePolicy::Frozen,
+            //expired tokens can be transferred until the owner turns on the validity policy
+            enforce_validity: false,
+            //nobody has approved an operator yet
+            operators_per_owner: LookupMap::new(StorageKey::OperatorsPerOwner),
         };
 
         //return the Contract object
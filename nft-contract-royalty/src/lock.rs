@@ -0,0 +1,147 @@
+use crate::*;
+
+//a lock that an owner put on their token, for example while it's staked. While the lock is active,
+//the token can't be transferred, approved or burned but the owner still holds it
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+pub struct TokenLock {
+    //the account the token is locked to. This account can unlock the token at any time
+    pub locked_by: AccountId,
+    //when the lock ends, Unix epoch in milliseconds. If this is None, the token stays locked until it's unlocked
+    pub until: Option<u64>,
+}
+
+impl TokenLock {
+    //whether the lock is still in effect at the given time
+    pub(crate) fn is_active(&self, now: u64) -> bool {
+        self.until.map_or(true, |until| now < until)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //lock a token to an account until a timestamp or until that account unlocks it. Only the owner can call this
+    #[payable]
+    pub fn nft_lock(
+        &mut self,
+        token_id: TokenId,
+        locked_by: AccountId,
+        until: Option<u64>,
+        memo: Option<String>,
+    ) {
+        //assert at least one yocto for security reasons and to pay for the storage of the lock
+        assert_at_least_one_yocto();
+
+        //make sure the caller is the owner and the token isn't locked already
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token.owner_id,
+            "Predecessor must be the token owner."
+        );
+        assert_not_locked(&token);
+        if let Some(until) = until {
+            assert!(
+                until > env::block_timestamp_ms(),
+                "The lock must end in the future"
+            );
+        }
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        token.lock = Some(TokenLock {
+            locked_by: locked_by.clone(),
+            until,
+        });
+        self.tokens_by_id.insert(&token_id, &token);
+
+        //charge or refund the difference in storage
+        refund_storage_difference(initial_storage_usage);
+
+        // Construct the lock log.
+        let nft_lock_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftLock(vec![NftLockLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // The account the token is locked to.
+                locked_by: locked_by.to_string(),
+                // Vector of token IDs that were locked.
+                token_ids: vec![token_id],
+                // When the lock ends.
+                until,
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_lock_log.to_string());
+    }
+
+    //unlock a token. The account the token is locked to can call this at any time, and the owner can call it
+    //once the lock has ended. The storage used by the lock is refunded to the owner
+    #[payable]
+    pub fn nft_unlock(&mut self, token_id: TokenId, memo: Option<String>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        let lock = token.lock.clone().expect("Token is not locked");
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == lock.locked_by
+                || (caller_id == token.owner_id && !lock.is_active(env::block_timestamp_ms())),
+            "Only the locking account can unlock the token before the lock ends"
+        );
+
+        //remove the lock and refund the owner the storage it used
+        let initial_storage_usage = env::storage_usage();
+        token.lock = None;
+        self.tokens_by_id.insert(&token_id, &token);
+        let released_storage = initial_storage_usage.saturating_sub(env::storage_usage());
+        let refund = env::storage_byte_cost().saturating_mul(released_storage.into());
+        if refund.gt(&ONE_YOCTONEAR) {
+            Promise::new(token.owner_id.clone()).transfer(refund);
+        }
+
+        // Construct the unlock log.
+        let nft_unlock_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftUnlock(vec![NftUnlockLog {
+                // Owner of the token.
+                owner_id: token.owner_id.to_string(),
+                // The account the token was locked to.
+                locked_by: lock.locked_by.to_string(),
+                // Vector of token IDs that were unlocked.
+                token_ids: vec![token_id],
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_unlock_log.to_string());
+    }
+}
+
+//make sure the token isn't locked. A lock that has ended doesn't count
+pub(crate) fn assert_not_locked(token: &Token) {
+    assert!(
+        token
+            .lock
+            .as_ref()
+            .map_or(true, |lock| !lock.is_active(env::block_timestamp_ms())),
+        "Token is locked"
+    );
+}
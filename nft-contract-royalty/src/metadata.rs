@@ -43,12 +43,23 @@ pub struct TokenMetadata {
 pub struct Token {
     //owner of the token
     pub owner_id: AccountId,
-    //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID
-    pub approved_account_ids: HashMap<AccountId, u64>,
+    //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval
+    //which holds the approval ID and when the approval expires
+    pub approved_account_ids: HashMap<AccountId, Approval>,
     //the next approval ID to give out.
     pub next_approval_id: u64,
     //keep track of the royalty percentages for the token in a hash map
     pub royalty: HashMap<AccountId, u32>,
+    //the account that minted the token
+    pub creator_id: AccountId,
+    //whether the token's metadata has been frozen. Once frozen, the metadata can never be updated again
+    pub metadata_frozen: bool,
+    //whether the token can be transferred. Soulbound tokens can't be transferred or approved
+    pub transferable: bool,
+    //the account a soulbound token's owner asked to move the token to. The creator has to approve the recovery
+    pub pending_recovery: Option<AccountId>,
+    //the lock on the token, if the owner locked it to an account
+    pub lock: Option<TokenLock>,
 }
 
 //The Json token is what will be returned from view calls.
@@ -61,10 +72,38 @@ pub struct JsonToken {
     pub owner_id: AccountId,
     //token metadata
     pub metadata: TokenMetadata,
-    //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID
+    //list of approved account IDs that have access to transfer the token. This maps an account ID to an approval ID.
+    //approvals that have expired aren't included
     pub approved_account_ids: HashMap<AccountId, u64>,
     //keep track of the royalty percentages for the token in a hash map
     pub royalty: HashMap<AccountId, u32>,
+    //the lock on the token if it's currently locked
+    pub lock: Option<TokenLock>,
+    //whether the token has started and hasn't expired, based on the starts_at and expires_at in its metadata
+    pub is_valid: bool,
+}
+
+impl NFTContractMetadata {
+    //make sure the contract metadata follows the metadata standard
+    pub(crate) fn assert_valid(&self) {
+        //the spec has to be an NFT metadata spec such as "nft-1.0.0"
+        assert!(
+            self.spec.starts_with("nft-"),
+            "Spec must be an NFT metadata spec such as \"nft-1.0.0\""
+        );
+        //the reference hash is the sha256 hash of the JSON the reference points to
+        if let Some(reference_hash) = &self.reference_hash {
+            assert!(
+                self.reference.is_some(),
+                "Reference hash requires a reference"
+            );
+            assert_eq!(
+                reference_hash.0.len(),
+                32,
+                "Reference hash must be a base64-encoded sha256 hash"
+            );
+        }
+    }
 }
 
 pub trait NonFungibleTokenMetadata {
@@ -0,0 +1,291 @@
+use crate::*;
+
+//who is allowed to update the metadata of tokens on the contract
+#[derive(BorshDeserialize, BorshSerialize, Serialize, Deserialize, NearSchema, Clone, Copy, PartialEq, Debug)]
+#[borsh(crate = "near_sdk::borsh")]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataUpdatePolicy {
+    //only the account that minted the token can update its metadata
+    Creator,
+    //only the current owner of the token can update its metadata
+    Owner,
+    //nobody can update token metadata
+    Frozen,
+}
+
+#[near_bindgen]
+impl Contract {
+    //set who is allowed to update token metadata. Only the contract owner can call this
+    pub fn set_metadata_update_policy(&mut self, policy: MetadataUpdatePolicy) {
+        self.assert_contract_owner();
+        self.metadata_update_policy = policy;
+    }
+
+    //get who is allowed to update token metadata
+    pub fn get_metadata_update_policy(&self) -> MetadataUpdatePolicy {
+        self.metadata_update_policy
+    }
+
+    //check if a token's metadata has been frozen for good
+    pub fn is_metadata_frozen(&self, token_id: TokenId) -> bool {
+        self.tokens_by_id
+            .get(&token_id)
+            .expect("No token")
+            .metadata_frozen
+    }
+
+    //replace the metadata for a token. updated_at is set to the current time. If the new metadata takes up
+    //more storage, the caller has to attach enough to cover it. If it takes up less, the caller is refunded
+    #[payable]
+    pub fn nft_update_metadata(
+        &mut self,
+        token_id: TokenId,
+        token_metadata: TokenMetadata,
+        memo: Option<String>,
+    ) {
+        //assert at least one yocto for security reasons and to pay for any extra storage
+        assert_at_least_one_yocto();
+
+        //make sure the caller is allowed to update the token's metadata
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        self.assert_can_update_metadata(&token);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //replace the metadata and set when it was updated
+        let mut token_metadata = token_metadata;
+        token_metadata.updated_at = Some(env::block_timestamp_ms());
+        self.token_metadata_by_id.insert(&token_id, &token_metadata);
+
+        //charge or refund the difference in storage
+        refund_storage_difference(initial_storage_usage);
+
+        //log the metadata update
+        log_metadata_update(vec![token_id], memo);
+    }
+
+    //freeze a token's metadata so that it can never be updated again. This can't be undone. The token's creator
+    //or owner can freeze it whatever the metadata update policy is
+    #[payable]
+    pub fn freeze_metadata(&mut self, token_id: TokenId, memo: Option<String>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //make sure the caller is the token's creator or owner
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == token.creator_id || caller_id == token.owner_id,
+            "Only the creator or the owner can freeze the token metadata"
+        );
+        assert!(!token.metadata_frozen, "Token metadata is frozen");
+
+        //freeze the metadata. The token takes up the same amount of storage so there's nothing to charge
+        token.metadata_frozen = true;
+        self.tokens_by_id.insert(&token_id, &token);
+
+        //log the metadata update so indexers know the token changed
+        log_metadata_update(vec![token_id], memo);
+    }
+
+    //update the contract's icon, base_uri, reference and reference_hash. Only the contract owner can call this.
+    //fields that aren't passed in are left as they are. A new reference replaces the reference hash as well,
+    //since the old hash belongs to the old reference. If the new metadata takes up more storage, the owner
+    //has to attach enough to cover it
+    #[payable]
+    pub fn update_contract_metadata(
+        &mut self,
+        icon: Option<String>,
+        base_uri: Option<String>,
+        reference: Option<String>,
+        reference_hash: Option<Base64VecU8>,
+        memo: Option<String>,
+    ) {
+        //assert at least one yocto for security reasons and to pay for any extra storage
+        assert_at_least_one_yocto();
+        self.assert_contract_owner();
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //replace the fields that were passed in and make sure the metadata is still valid
+        let mut metadata = self.metadata.get().unwrap();
+        if icon.is_some() {
+            metadata.icon = icon;
+        }
+        if base_uri.is_some() {
+            metadata.base_uri = base_uri;
+        }
+        if reference.is_some() {
+            metadata.reference = reference;
+            metadata.reference_hash = reference_hash;
+        } else if reference_hash.is_some() {
+            metadata.reference_hash = reference_hash;
+        }
+        metadata.assert_valid();
+        self.metadata.set(&metadata);
+
+        //charge or refund the difference in storage
+        refund_storage_difference(initial_storage_usage);
+
+        // Construct the contract metadata update log as per the events standard.
+        let contract_metadata_update_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // The metadata update events were added in version 1.1.0 of the standard.
+            version: NFT_METADATA_UPDATE_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::ContractMetadataUpdate(vec![ContractMetadataUpdateLog {
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&contract_metadata_update_log.to_string());
+    }
+}
+
+impl Contract {
+    //make sure the predecessor is allowed to update the token's metadata under the current policy
+    pub(crate) fn assert_can_update_metadata(&self, token: &Token) {
+        assert!(!token.metadata_frozen, "Token metadata is frozen");
+
+        let caller_id = env::predecessor_account_id();
+        match self.metadata_update_policy {
+            MetadataUpdatePolicy::Creator => assert_eq!(
+                caller_id, token.creator_id,
+                "Only the creator can update the token metadata"
+            ),
+            MetadataUpdatePolicy::Owner => assert_eq!(
+                caller_id, token.owner_id,
+                "Only the owner can update the token metadata"
+            ),
+            MetadataUpdatePolicy::Frozen => env::panic_str("Token metadata updates are disabled"),
+        }
+    }
+}
+
+//log the metadata update for the given tokens as per the events standard
+pub(crate) fn log_metadata_update(token_ids: Vec<TokenId>, memo: Option<String>) {
+    // Construct the metadata update log as per the events standard.
+    let nft_metadata_update_log: EventLog = EventLog {
+        // Standard name ("nep171").
+        standard: NFT_STANDARD_NAME.to_string(),
+        // The metadata update event was added in version 1.1.0 of the standard.
+        version: NFT_METADATA_UPDATE_SPEC.to_string(),
+        // The data related with the event stored in a vector.
+        event: EventLogVariant::NftMetadataUpdate(vec![NftMetadataUpdateLog {
+            // Vector of token IDs that were updated.
+            token_ids,
+            // An optional memo to include.
+            memo,
+        }]),
+    };
+
+    // Log the serialized json.
+    env::log_str(&nft_metadata_update_log.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup() -> Contract {
+        let mut context = VMContextBuilder::new();
+        context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(NearToken::from_near(1));
+        testing_env!(context.build());
+        Contract::new_default_meta(accounts(1))
+    }
+
+    fn token_metadata() -> TokenMetadata {
+        TokenMetadata {
+            title: Some("Token".to_string()),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    #[test]
+    fn update_contract_metadata_keeps_fields_that_are_not_passed_in() {
+        let mut contract = setup();
+        contract.update_contract_metadata(
+            Some("data:image/svg+xml,icon".to_string()),
+            Some("https://gateway.example".to_string()),
+            Some("https://example.com/collection.json".to_string()),
+            Some(Base64VecU8(vec![1; 32])),
+            None,
+        );
+
+        //only the icon is updated
+        contract.update_contract_metadata(Some("data:image/svg+xml,new".to_string()), None, None, None, None);
+
+        let metadata = contract.metadata.get().unwrap();
+        assert_eq!(metadata.icon, Some("data:image/svg+xml,new".to_string()));
+        assert_eq!(metadata.base_uri, Some("https://gateway.example".to_string()));
+        assert_eq!(metadata.reference, Some("https://example.com/collection.json".to_string()));
+        assert_eq!(metadata.reference_hash, Some(Base64VecU8(vec![1; 32])));
+    }
+
+    #[test]
+    fn update_contract_metadata_replaces_the_hash_with_the_reference() {
+        let mut contract = setup();
+        contract.update_contract_metadata(
+            None,
+            None,
+            Some("https://example.com/collection.json".to_string()),
+            Some(Base64VecU8(vec![1; 32])),
+            None,
+        );
+
+        //the old hash doesn't belong to the new reference so it's dropped
+        contract.update_contract_metadata(None, None, Some("https://example.com/new.json".to_string()), None, None);
+
+        let metadata = contract.metadata.get().unwrap();
+        assert_eq!(metadata.reference, Some("https://example.com/new.json".to_string()));
+        assert!(metadata.reference_hash.is_none());
+    }
+
+    #[test]
+    fn owner_can_freeze_metadata_while_updates_are_disabled() {
+        let mut contract = setup();
+        contract.nft_mint("1".to_string(), accounts(2), token_metadata(), None, None);
+
+        //the metadata update policy is Frozen by default, yet the token's owner can still freeze it
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(2))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.freeze_metadata("1".to_string(), None);
+
+        assert!(contract.tokens_by_id.get(&"1".to_string()).unwrap().metadata_frozen);
+    }
+
+    #[test]
+    #[should_panic(expected = "Only the creator or the owner can freeze the token metadata")]
+    fn others_cannot_freeze_metadata() {
+        let mut contract = setup();
+        contract.nft_mint("1".to_string(), accounts(2), token_metadata(), None, None);
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(3))
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.freeze_metadata("1".to_string(), None);
+    }
+}
@@ -0,0 +1,160 @@
+use crate::*;
+
+//the state of the contract before minting was restricted and tokens could be updated, locked or made soulbound
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub tokens_by_id: LookupMap<TokenId, OldToken>,
+    pub token_metadata_by_id: UnorderedMap<TokenId, TokenMetadata>,
+    pub metadata: LazyOption<NFTContractMetadata>,
+}
+
+//a token as it was stored before approvals could expire and tokens kept track of their creator
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldToken {
+    pub owner_id: AccountId,
+    pub approved_account_ids: HashMap<AccountId, u64>,
+    pub next_approval_id: u64,
+    pub royalty: HashMap<AccountId, u32>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        migrate the contract and its tokens to the current layout. Every token is read in the old layout and
+        written again under the same key. Its approvals don't expire, it stays transferable and unlocked, and since
+        the creator of old tokens wasn't stored, the contract owner is recorded as their creator. Every setting and
+        collection that didn't exist before starts out the same as on a new contract. This touches every token so
+        the GAS it needs grows with the number of tokens on the contract.
+    */
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old_state: OldContract = env::state_read().expect("Failed to read the old state");
+
+        /*
+            the old and the new tokens use the same prefix. Each old token is removed before it's written again so that
+            the old bytes are never read in the new layout
+        */
+        let mut tokens_by_id: LookupMap<TokenId, Token> = LookupMap::new(StorageKey::TokensById);
+        for token_id in old_state.token_metadata_by_id.keys() {
+            let old_token = match old_state.tokens_by_id.remove(&token_id) {
+                Some(old_token) => old_token,
+                None => continue,
+            };
+            tokens_by_id.insert(
+                &token_id,
+                &Token {
+                    owner_id: old_token.owner_id,
+                    approved_account_ids: old_token
+                        .approved_account_ids
+                        .into_iter()
+                        .map(|(account_id, approval_id)| {
+                            (
+                                account_id,
+                                Approval {
+                                    approval_id,
+                                    expires_at: None,
+                                },
+                            )
+                        })
+                        .collect(),
+                    next_approval_id: old_token.next_approval_id,
+                    royalty: old_token.royalty,
+                    creator_id: old_state.owner_id.clone(),
+                    metadata_frozen: false,
+                    transferable: true,
+                    pending_recovery: None,
+                    lock: None,
+                },
+            );
+        }
+
+        Self {
+            owner_id: old_state.owner_id,
+            tokens_per_owner: old_state.tokens_per_owner,
+            tokens_by_id,
+            token_metadata_by_id: old_state.token_metadata_by_id,
+            metadata: old_state.metadata,
+            minters: UnorderedMap::new(StorageKey::Minters),
+            open_mint: false,
+            metadata_update_policy: MetadataUpdatePolicy::Frozen,
+            enforce_validity: false,
+            operators_per_owner: LookupMap::new(StorageKey::OperatorsPerOwner),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn migrate_tokens_to_the_current_layout() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+
+        //write the state the way the old contract stored it, with a token that has an approval and a royalty
+        let mut tokens_by_id = LookupMap::new(StorageKey::TokensById);
+        tokens_by_id.insert(
+            &"1".to_string(),
+            &OldToken {
+                owner_id: accounts(1),
+                approved_account_ids: HashMap::from([(accounts(2), 3)]),
+                next_approval_id: 4,
+                royalty: HashMap::from([(accounts(3), 1_000)]),
+            },
+        );
+        let mut token_metadata_by_id = UnorderedMap::new(StorageKey::TokenMetadataById);
+        token_metadata_by_id.insert(
+            &"1".to_string(),
+            &TokenMetadata {
+                title: Some("Token".to_string()),
+                description: None,
+                media: None,
+                media_hash: None,
+                copies: None,
+                issued_at: None,
+                expires_at: None,
+                starts_at: None,
+                updated_at: None,
+                extra: None,
+                reference: None,
+                reference_hash: None,
+            },
+        );
+        let old_state = OldContract {
+            owner_id: accounts(0),
+            tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner),
+            tokens_by_id,
+            token_metadata_by_id,
+            metadata: LazyOption::new(StorageKey::NFTContractMetadata, None),
+        };
+        env::state_write(&old_state);
+
+        let contract = Contract::migrate();
+
+        //the token keeps its owner, approvals and royalty and gets the defaults for everything new
+        let token = contract.tokens_by_id.get(&"1".to_string()).unwrap();
+        assert_eq!(token.owner_id, accounts(1));
+        let approval = token.approved_account_ids.get(&accounts(2)).unwrap();
+        assert_eq!(approval.approval_id, 3);
+        assert!(approval.expires_at.is_none());
+        assert_eq!(token.next_approval_id, 4);
+        assert_eq!(token.royalty.get(&accounts(3)), Some(&1_000));
+        assert_eq!(token.creator_id, accounts(0));
+        assert!(token.transferable);
+        assert!(!token.metadata_frozen);
+        assert!(token.lock.is_none());
+
+        //only the owner can mint until minters are added
+        assert!(!contract.is_open_mint());
+        assert_eq!(contract.token_metadata_by_id.len(), 1);
+    }
+}
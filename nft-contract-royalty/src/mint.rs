@@ -1,4 +1,28 @@
 use crate::*;
+use near_sdk::Gas;
+
+//GAS that has to be left before minting another token in a batch. This covers minting the token itself
+//plus logging the mint events and refunding the deposit once the batch is done
+const GAS_FOR_BATCH_MINT_TOKEN: Gas = Gas::from_tgas(5);
+const GAS_FOR_BATCH_MINT_FINISH: Gas = Gas::from_tgas(10);
+//how many bytes a single call can log in total. The batch stops before the mint event gets longer than this
+const MAX_TOTAL_LOG_LENGTH: usize = 16384;
+
+//a single token that should be minted as part of nft_batch_mint
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct BatchMintItem {
+    //the ID of the token to mint
+    pub token_id: TokenId,
+    //the account that will own the token
+    pub token_owner_id: AccountId,
+    //the metadata for the token
+    pub token_metadata: TokenMetadata,
+    //optional perpetual royalties for the token. Each receiver can only be listed once
+    pub perpetual_royalties: Option<Vec<(AccountId, u32)>>,
+    //whether the token can be transferred. Defaults to true
+    pub transferable: Option<bool>,
+}
 
 #[near_bindgen]
 impl Contract {
@@ -9,27 +33,180 @@ impl Contract {
         token_owner_id: AccountId,
         token_metadata: TokenMetadata,
         //we add an optional parameter for perpetual royalties
-        perpetual_royalties: Option<HashMap<AccountId, u32>>,
+        perpetual_royalties: Option<Vec<(AccountId, u32)>>,
+        //whether the token can be transferred. Defaults to true. Soulbound tokens are minted with false
+        transferable: Option<bool>,
     ) {
+        //make sure the caller is allowed to mint
+        self.internal_use_mint_quota(1);
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //create the token and add it to the owner
+        self.internal_mint(
+            token_id.clone(),
+            token_owner_id.clone(),
+            token_metadata,
+            perpetual_royalties,
+            transferable.unwrap_or(true),
+        );
+
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftMint(vec![NftMintLog {
+                // Owner of the token.
+                owner_id: token_owner_id.to_string(),
+                // Vector of token IDs that were minted.
+                token_ids: vec![token_id.to_string()],
+                // An optional memo to include.
+                memo: None,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
+
+        //calculate the required storage which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(required_storage_in_bytes.into());
+    }
+
+    //mint several tokens at once. The storage for the whole batch is measured and refunded once, and a single
+    //mint event is logged with the token IDs grouped by owner. If the GAS runs low or the event would get longer
+    //than a call can log, the rest of the tokens are left for another call.
+    //returns the IDs of the tokens that were minted
+    #[payable]
+    pub fn nft_batch_mint(&mut self, tokens: Vec<BatchMintItem>) -> Vec<TokenId> {
+        assert!(!tokens.is_empty(), "Must mint at least 1 token");
+        //make sure the caller is allowed to mint the whole batch before anything is written
+        self.assert_can_mint(tokens.len().try_into().unwrap_or(u32::MAX));
+
         //measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
 
-        // create a royalty map to store in the token
-        let mut royalty = HashMap::new();
+        //the minted token IDs grouped by owner. Owners are kept in the order they first appear in the batch
+        let mut minted = Vec::new();
+        let mut mint_logs: Vec<NftMintLog> = Vec::new();
+        let mut mint_log_length = empty_mint_log_length();
+
+        for item in tokens {
+            //stop once there isn't enough GAS left to mint another token and finish the batch. The caller can
+            //see which tokens were minted from the return value and pass in the rest again
+            let remaining_gas = env::prepaid_gas().saturating_sub(env::used_gas());
+            if remaining_gas < GAS_FOR_BATCH_MINT_TOKEN.saturating_add(GAS_FOR_BATCH_MINT_FINISH) {
+                break;
+            }
+
+            //stop as well if listing the token would make the mint event too long to log
+            let owner_id = item.token_owner_id.to_string();
+            let owner_index = mint_logs.iter().position(|log| log.owner_id == owner_id);
+            let added_length = added_mint_log_length(&owner_id, &item.token_id, owner_index.is_some());
+            if mint_log_length + added_length > MAX_TOTAL_LOG_LENGTH {
+                break;
+            }
+            mint_log_length += added_length;
 
-        // if perpetual royalties were passed into the function:
-        if let Some(perpetual_royalties) = perpetual_royalties {
-            //make sure that the length of the perpetual royalties is below 7 since we won't have enough GAS to pay out that many people
-            assert!(
-                perpetual_royalties.len() < 7,
-                "Cannot add more than 6 perpetual royalty amounts"
+            //create the token and add it to the owner
+            self.internal_mint(
+                item.token_id.clone(),
+                item.token_owner_id,
+                item.token_metadata,
+                item.perpetual_royalties,
+                item.transferable.unwrap_or(true),
             );
 
-            //iterate through the perpetual royalties and insert the account and amount in the royalty map
-            for (account, amount) in perpetual_royalties {
-                royalty.insert(account, amount);
+            //add the token ID to the owner's mint log
+            match owner_index {
+                Some(index) => mint_logs[index].token_ids.push(item.token_id.clone()),
+                None => mint_logs.push(NftMintLog {
+                    owner_id,
+                    token_ids: vec![item.token_id.clone()],
+                    memo: None,
+                }),
             }
+            minted.push(item.token_id);
         }
+        assert!(
+            !minted.is_empty(),
+            "Not enough GAS to mint a single token. Attach more GAS"
+        );
+        //only the tokens that were actually minted count against the caller's quota
+        self.internal_use_mint_quota(minted.len() as u32);
+
+        // Construct the mint log as per the events standard.
+        let nft_mint_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // One entry per owner with all the token IDs that were minted for them.
+            event: EventLogVariant::NftMint(mint_logs),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_mint_log.to_string());
+
+        //calculate the required storage for the whole batch which was the used - initial
+        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+
+        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
+        refund_deposit(required_storage_in_bytes.into());
+
+        minted
+    }
+}
+
+//how long a logged mint event is before any entries are added to it
+fn empty_mint_log_length() -> usize {
+    let nft_mint_log = EventLog {
+        standard: NFT_STANDARD_NAME.to_string(),
+        version: NFT_METADATA_SPEC.to_string(),
+        event: EventLogVariant::NftMint(Vec::new()),
+    };
+    nft_mint_log.to_string().len()
+}
+
+//how much longer the mint event gets when a token is added to it. If the owner doesn't have an entry yet, the
+//entry is added as well. This counts a comma for every item, so it's never shorter than the real event
+fn added_mint_log_length(owner_id: &str, token_id: &TokenId, has_entry: bool) -> usize {
+    let token_id_length = serde_json::to_string(token_id).map_or(0, |json| json.len()) + 1;
+    if has_entry {
+        return token_id_length;
+    }
+
+    let entry = NftMintLog {
+        owner_id: owner_id.to_string(),
+        token_ids: Vec::new(),
+        memo: None,
+    };
+    serde_json::to_string(&entry).map_or(0, |json| json.len()) + 1 + token_id_length
+}
+
+impl Contract {
+    //create a token and add it to the owner (internal method and can't be called directly via CLI).
+    //this doesn't log the mint or charge for storage. The caller does that
+    pub(crate) fn internal_mint(
+        &mut self,
+        token_id: TokenId,
+        token_owner_id: AccountId,
+        token_metadata: TokenMetadata,
+        perpetual_royalties: Option<Vec<(AccountId, u32)>>,
+        transferable: bool,
+    ) {
+        // the perpetual royalties for the token. If no perpetual royalties were passed in, the owner gets 100%
+        let royalty = perpetual_royalties.unwrap_or_default();
+
+        //make sure there aren't too many receivers, no receiver is listed twice, every receiver gets more than 0 and
+        //the total is at most 100%
+        assert_valid_royalties(&royalty);
 
         //specify the token struct that contains the owner ID
         let token = Token {
@@ -40,7 +217,16 @@ impl Contract {
             //the next approval ID is set to 0
             next_approval_id: 0,
             //the map of perpetual royalties for the token (The owner will get 100% - total perpetual royalties)
-            royalty,
+            royalty: royalty.into_iter().collect(),
+            //the account minting the token is its creator
+            creator_id: env::predecessor_account_id(),
+            //the metadata can be updated until it's frozen
+            metadata_frozen: false,
+            transferable,
+            //nobody has asked to recover the token yet
+            pending_recovery: None,
+            //the token isn't locked
+            lock: None,
         };
 
         //insert the token ID and token struct and make sure that the token doesn't exist
@@ -54,31 +240,146 @@ impl Contract {
 
         //call the internal method for adding the token to the owner
         self.internal_add_token_to_owner(&token.owner_id, &token_id);
+    }
+}
 
-        // Construct the mint log as per the events standard.
-        let nft_mint_log: EventLog = EventLog {
-            // Standard name ("nep171").
-            standard: NFT_STANDARD_NAME.to_string(),
-            // Version of the standard ("nft-1.0.0").
-            version: NFT_METADATA_SPEC.to_string(),
-            // The data related with the event stored in a vector.
-            event: EventLogVariant::NftMint(vec![NftMintLog {
-                // Owner of the token.
-                owner_id: token.owner_id.to_string(),
-                // Vector of token IDs that were minted.
-                token_ids: vec![token_id.to_string()],
-                // An optional memo to include.
-                memo: None,
-            }]),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn setup(prepaid_gas: Gas) -> Contract {
+        let mut context = VMContextBuilder::new();
+        context
+            .predecessor_account_id(accounts(1))
+            .attached_deposit(NearToken::from_near(100))
+            .prepaid_gas(prepaid_gas);
+        testing_env!(context.build());
+        Contract::new_default_meta(accounts(1))
+    }
+
+    fn batch(count: usize, token_id_length: usize) -> Vec<BatchMintItem> {
+        (0..count)
+            .map(|index| BatchMintItem {
+                token_id: format!("{:0>width$}", index, width = token_id_length),
+                //every other token goes to the same owner
+                token_owner_id: accounts(2 + index % 2),
+                token_metadata: TokenMetadata {
+                    title: None,
+                    description: None,
+                    media: None,
+                    media_hash: None,
+                    copies: None,
+                    issued_at: None,
+                    expires_at: None,
+                    starts_at: None,
+                    updated_at: None,
+                    extra: None,
+                    reference: None,
+                    reference_hash: None,
+                },
+                perpetual_royalties: None,
+                transferable: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn batch_mint_groups_token_ids_by_owner() {
+        let mut contract = setup(Gas::from_tgas(300));
+
+        let minted = contract.nft_batch_mint(batch(4, 1));
+
+        assert_eq!(minted, vec!["0", "1", "2", "3"]);
+        assert_eq!(
+            get_logs(),
+            vec![r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_mint","data":[{"owner_id":"charlie","token_ids":["0","2"]},{"owner_id":"danny","token_ids":["1","3"]}]}"#]
+        );
+    }
+
+    #[test]
+    fn batch_mint_stops_before_the_event_gets_too_long() {
+        let mut contract = setup(Gas::from_tgas(300));
+
+        //200 token IDs of 100 characters don't fit in one mint event
+        let tokens = batch(200, 100);
+        let token_ids: Vec<TokenId> = tokens.iter().map(|item| item.token_id.clone()).collect();
+        let minted = contract.nft_batch_mint(tokens);
+
+        //the tokens that fit are minted and logged, the rest are left for another call
+        assert!(!minted.is_empty() && minted.len() < token_ids.len());
+        assert_eq!(minted, token_ids[..minted.len()]);
+        assert!(contract.tokens_by_id.get(&token_ids[minted.len()]).is_none());
+
+        let logs = get_logs();
+        assert_eq!(logs.len(), 1);
+        assert!(logs[0].len() <= MAX_TOTAL_LOG_LENGTH);
+        let event: EventLog = serde_json::from_str(logs[0].strip_prefix("EVENT_JSON:").unwrap()).unwrap();
+        let EventLogVariant::NftMint(mint_logs) = event.event else {
+            panic!("Expected a mint event");
         };
+        let mut logged: Vec<TokenId> = mint_logs.into_iter().flat_map(|log| log.token_ids).collect();
+        logged.sort();
+        assert_eq!(logged, minted);
+    }
 
-        // Log the serialized json.
-        env::log_str(&nft_mint_log.to_string());
+    #[test]
+    fn batch_mint_returns_the_tokens_minted_before_gas_ran_low() {
+        //only a few tokens can be minted with this much GAS
+        let mut contract = setup(Gas::from_tgas(20));
 
-        //calculate the required storage which was the used - initial
-        let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
+        let tokens = batch(50, 1);
+        let token_ids: Vec<TokenId> = tokens.iter().map(|item| item.token_id.clone()).collect();
+        let minted = contract.nft_batch_mint(tokens);
 
-        //refund any excess storage if the user attached too much. Panic if they didn't attach enough to cover the required.
-        refund_deposit(required_storage_in_bytes.into());
+        //the tokens that were minted are returned in order and the rest are left for another call
+        assert!(!minted.is_empty() && minted.len() < token_ids.len());
+        assert_eq!(minted, token_ids[..minted.len()]);
+        for token_id in &token_ids[minted.len()..] {
+            assert!(contract.tokens_by_id.get(token_id).is_none());
+        }
+        assert_eq!(contract.nft_total_supply().0, minted.len() as u128);
+    }
+
+    #[test]
+    #[should_panic(expected = "Not enough GAS to mint a single token. Attach more GAS")]
+    fn batch_mint_needs_gas_for_one_token() {
+        let mut contract = setup(Gas::from_tgas(14));
+        contract.nft_batch_mint(batch(1, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Minting 4 tokens exceeds the remaining quota of 3")]
+    fn batch_mint_checks_the_quota_for_the_whole_batch() {
+        let mut contract = setup(Gas::from_tgas(300));
+        contract.add_minter(accounts(4), Some(3));
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(NearToken::from_near(100))
+            .prepaid_gas(Gas::from_tgas(300))
+            .build());
+        contract.nft_batch_mint(batch(4, 1));
+    }
+
+    #[test]
+    fn batch_mint_only_uses_the_quota_of_minted_tokens() {
+        let mut contract = setup(Gas::from_tgas(300));
+        contract.add_minter(accounts(4), Some(50));
+
+        //only a few tokens can be minted with this much GAS
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(4))
+            .attached_deposit(NearToken::from_near(100))
+            .prepaid_gas(Gas::from_tgas(20))
+            .build());
+        let minted = contract.nft_batch_mint(batch(50, 1));
+
+        assert!(minted.len() < 50);
+        assert_eq!(
+            contract.minters.get(&accounts(4)),
+            Some(Some(50 - minted.len() as u32))
+        );
     }
 }
@@ -1,4 +1,5 @@
 use crate::*;
+use crate::soulbound::bytes_for_pending_recovery;
 use near_sdk::{ext_contract, log, Gas, PromiseResult};
 
 const GAS_FOR_RESOLVE_TRANSFER: Gas = Gas::from_tgas(10);
@@ -59,7 +60,7 @@ trait NonFungibleTokenResolver {
         receiver_id: AccountId,
         token_id: TokenId,
         //we introduce the approval map so we can keep track of what the approvals were before the transfer
-        approved_account_ids: HashMap<AccountId, u64>,
+        approved_account_ids: HashMap<AccountId, Approval>,
         //we introduce a memo for logging the transfer event
         memo: Option<String>,
     ) -> bool;
@@ -159,13 +160,24 @@ impl NonFungibleTokenCore for Contract {
         if let Some(token) = self.tokens_by_id.get(&token_id) {
             //we'll get the metadata for that token
             let metadata = self.token_metadata_by_id.get(&token_id).unwrap();
+            //the token's lock and validity depend on the current time
+            let now = env::block_timestamp_ms();
             //we return the JsonToken (wrapped by Some since we return an option)
             Some(JsonToken {
                 token_id,
                 owner_id: token.owner_id,
+                is_valid: metadata.is_valid(now),
                 metadata,
-                approved_account_ids: token.approved_account_ids,
+                //only show the approvals that haven't expired
+                approved_account_ids: token
+                    .approved_account_ids
+                    .into_iter()
+                    .filter(|(_, approval)| !approval.is_expired(now))
+                    .map(|(account_id, approval)| (account_id, approval.approval_id))
+                    .collect(),
                 royalty: token.royalty,
+                //only show the lock if it's still in effect
+                lock: token.lock.filter(|lock| lock.is_active(now)),
             })
         } else {
             //if there wasn't a token ID in the tokens_by_id collection, we return None
@@ -187,7 +199,7 @@ impl NonFungibleTokenResolver for Contract {
         receiver_id: AccountId,
         token_id: TokenId,
         //we introduce the approval map so we can keep track of what the approvals were before the transfer
-        approved_account_ids: HashMap<AccountId, u64>,
+        approved_account_ids: HashMap<AccountId, Approval>,
         //we introduce a memo for logging the transfer event
         memo: Option<String>,
     ) -> bool {
@@ -246,9 +258,48 @@ impl NonFungibleTokenResolver for Contract {
         //reset the approved account IDs to what they were before the transfer
         token.approved_account_ids = approved_account_ids;
 
+        /*
+            the receiver may have locked the token or asked to recover it while they owned it. Neither can outlive
+            the revert, otherwise the original owner would get back a token that the receiver still controls. We
+            remove them and refund the receiver the storage they paid for.
+        */
+        let lock = token.lock.take();
+        let pending_recovery = token.pending_recovery.take();
+        let released_storage = lock.as_ref().map_or(0, |lock| {
+            near_sdk::borsh::object_length(lock).unwrap_or(0) as u128
+        }) + pending_recovery
+            .as_ref()
+            .map_or(0, bytes_for_pending_recovery);
+        let refund = env::storage_byte_cost().saturating_mul(released_storage);
+        if refund.gt(&ONE_YOCTONEAR) {
+            Promise::new(receiver_id.clone()).transfer(refund);
+        }
+
         //we inset the token back into the tokens_by_id collection
         self.tokens_by_id.insert(&token_id, &token);
 
+        //if the receiver had locked the token, we log that it was unlocked
+        if let Some(lock) = lock {
+            let nft_unlock_log: EventLog = EventLog {
+                // Standard name ("nep171").
+                standard: NFT_STANDARD_NAME.to_string(),
+                // Version of the standard ("nft-1.0.0").
+                version: NFT_METADATA_SPEC.to_string(),
+                // The data related with the event stored in a vector.
+                event: EventLogVariant::NftUnlock(vec![NftUnlockLog {
+                    // The receiver owned the token when it was locked.
+                    owner_id: receiver_id.to_string(),
+                    // The account the token was locked to.
+                    locked_by: lock.locked_by.to_string(),
+                    // Vector of token IDs that were unlocked.
+                    token_ids: vec![token_id.to_string()],
+                    // An optional memo to include.
+                    memo: Some("transfer reverted".to_string()),
+                }]),
+            };
+            env::log_str(&nft_unlock_log.to_string());
+        }
+
         /*
             We need to log that the NFT was reverted back to the original owner.
             The old_owner_id will be the receiver and the new_owner_id will be the
@@ -281,3 +332,72 @@ impl NonFungibleTokenResolver for Contract {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn set_context(predecessor_id: AccountId, deposit: NearToken) -> VMContextBuilder {
+        let mut context = VMContextBuilder::new();
+        context
+            .predecessor_account_id(predecessor_id)
+            .attached_deposit(deposit);
+        testing_env!(context.build());
+        context
+    }
+
+    fn token_metadata() -> TokenMetadata {
+        TokenMetadata {
+            title: Some("Token".to_string()),
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at: None,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    #[test]
+    fn reverted_transfer_clears_the_receivers_lock() {
+        set_context(accounts(1), NearToken::from_near(1));
+        let mut contract = Contract::new_default_meta(accounts(1));
+        contract.internal_mint("1".to_string(), accounts(1), token_metadata(), None, true);
+
+        //the token is transferred to the receiver who locks it before nft_on_transfer asks for it back
+        contract.internal_transfer(&accounts(1), &accounts(2), &"1".to_string(), None, None);
+        set_context(accounts(2), NearToken::from_near(1));
+        contract.nft_lock("1".to_string(), accounts(3), None, None);
+        assert!(contract.tokens_by_id.get(&"1".to_string()).unwrap().lock.is_some());
+
+        let context = set_context(accounts(0), NearToken::from_yoctonear(0));
+        testing_env!(
+            context.build(),
+            near_sdk::test_vm_config(),
+            near_sdk::RuntimeFeesConfig::test(),
+            HashMap::default(),
+            vec![PromiseResult::Successful(b"true".to_vec())]
+        );
+        let transferred = contract.nft_resolve_transfer(
+            None,
+            accounts(1),
+            accounts(2),
+            "1".to_string(),
+            HashMap::new(),
+            None,
+        );
+
+        assert!(!transferred);
+        let token = contract.tokens_by_id.get(&"1".to_string()).unwrap();
+        assert_eq!(token.owner_id, accounts(1));
+        assert!(token.lock.is_none());
+        assert!(token.pending_recovery.is_none());
+    }
+}
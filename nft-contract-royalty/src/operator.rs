@@ -0,0 +1,123 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //approve an operator to transfer all of the caller's tokens, including tokens they get later. The caller has
+    //to attach enough to cover the storage used by the approval. Any excess is refunded
+    #[payable]
+    pub fn nft_approve_all(&mut self, operator_id: AccountId) {
+        //assert at least one yocto for security reasons and to pay for the storage of the approval
+        assert_at_least_one_yocto();
+
+        let owner_id = env::predecessor_account_id();
+        assert_ne!(owner_id, operator_id, "The owner can't be their own operator");
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        //get the set of operators for the owner. If they don't have any, we create a new set
+        let mut operators = self.operators_per_owner.get(&owner_id).unwrap_or_else(|| {
+            UnorderedSet::new(StorageKey::OperatorsPerOwnerInner {
+                //we get a new unique prefix for the collection
+                account_id_hash: hash_account_id(&owner_id),
+            })
+        });
+        assert!(operators.insert(&operator_id), "Operator is already approved");
+        self.operators_per_owner.insert(&owner_id, &operators);
+
+        //charge or refund the difference in storage
+        refund_storage_difference(initial_storage_usage);
+
+        log_operator_event(EventLogVariant::NftApproveAll(vec![NftOperatorLog {
+            owner_id: owner_id.to_string(),
+            operator_id: operator_id.to_string(),
+        }]));
+    }
+
+    //revoke an operator from transferring the caller's tokens. The storage used by the approval is refunded
+    #[payable]
+    pub fn nft_revoke_operator(&mut self, operator_id: AccountId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let owner_id = env::predecessor_account_id();
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        let mut operators = self
+            .operators_per_owner
+            .get(&owner_id)
+            .expect("Operator is not approved");
+        assert!(operators.remove(&operator_id), "Operator is not approved");
+
+        //if the owner doesn't have any operators left, we remove them from the map
+        if operators.is_empty() {
+            self.operators_per_owner.remove(&owner_id);
+        } else {
+            self.operators_per_owner.insert(&owner_id, &operators);
+        }
+
+        //refund the released storage along with the attached yoctoNEAR
+        refund_storage_difference(initial_storage_usage);
+
+        log_operator_event(EventLogVariant::NftRevokeOperator(vec![NftOperatorLog {
+            owner_id: owner_id.to_string(),
+            operator_id: operator_id.to_string(),
+        }]));
+    }
+
+    //check if an account is an operator for all of an owner's tokens
+    pub fn nft_is_operator(&self, owner_id: AccountId, operator_id: AccountId) -> bool {
+        self.is_operator(&owner_id, &operator_id)
+    }
+
+    //Query for the operators an owner has approved using pagination
+    pub fn nft_operators_for_owner(
+        &self,
+        account_id: AccountId,
+        from_index: Option<U128>,
+        limit: Option<u64>,
+    ) -> Vec<AccountId> {
+        //if the owner hasn't approved any operators, we simply return an empty vector
+        let operators = match self.operators_per_owner.get(&account_id) {
+            Some(operators) => operators,
+            None => return vec![],
+        };
+
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        operators
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            .collect()
+    }
+}
+
+impl Contract {
+    //whether the operator is approved to transfer all of the owner's tokens
+    pub(crate) fn is_operator(&self, owner_id: &AccountId, operator_id: &AccountId) -> bool {
+        self.operators_per_owner
+            .get(owner_id)
+            .map_or(false, |operators| operators.contains(operator_id))
+    }
+}
+
+//log an operator approval or revocation as per the events standard
+fn log_operator_event(event: EventLogVariant) {
+    let operator_log: EventLog = EventLog {
+        // Standard name ("nep171").
+        standard: NFT_STANDARD_NAME.to_string(),
+        // Version of the standard ("nft-1.0.0").
+        version: NFT_METADATA_SPEC.to_string(),
+        // The data related with the event stored in a vector.
+        event,
+    };
+
+    // Log the serialized json.
+    env::log_str(&operator_log.to_string());
+}
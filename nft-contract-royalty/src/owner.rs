@@ -0,0 +1,109 @@
+use crate::*;
+
+//The Json minter is what will be returned from view calls.
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonMinter {
+    //the approved minter
+    pub account_id: AccountId,
+    //how many more tokens the minter can mint. If this is None, the minter has no quota
+    pub remaining_quota: Option<u32>,
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Add a specified account as an approved minter. If a quota is passed in, the minter can only
+    /// mint that many more tokens. Adding a minter that already exists replaces their quota
+    pub fn add_minter(&mut self, account_id: AccountId, quota: Option<u32>) {
+        self.assert_contract_owner();
+        self.minters.insert(&account_id, &quota);
+    }
+
+    /// Remove a specified account as an approved minter
+    pub fn remove_minter(&mut self, account_id: AccountId) {
+        self.assert_contract_owner();
+        self.minters.remove(&account_id);
+    }
+
+    /// Allow anyone to mint (true) or only the owner and approved minters (false)
+    pub fn set_open_mint(&mut self, open_mint: bool) {
+        self.assert_contract_owner();
+        self.open_mint = open_mint;
+    }
+
+    /// Check if anyone can mint on the contract
+    pub fn is_open_mint(&self) -> bool {
+        self.open_mint
+    }
+
+    /// Check if a specified account is an approved minter
+    pub fn is_minter(&self, account_id: AccountId) -> bool {
+        self.minters.get(&account_id).is_some()
+    }
+
+    /// Get the approved minters and their remaining quotas using pagination
+    pub fn get_minters(&self, from_index: Option<U128>, limit: Option<u64>) -> Vec<JsonMinter> {
+        //where to start pagination - if we have a from_index, we'll use that - otherwise start from 0 index
+        let start = u128::from(from_index.unwrap_or(U128(0)));
+
+        //iterate through the minters
+        self.minters
+            .iter()
+            //skip to the index we specified in the start variable
+            .skip(start as usize)
+            //take the first "limit" elements in the vector. If we didn't specify a limit, use 50
+            .take(limit.unwrap_or(50) as usize)
+            //we'll map the minters into Json minters
+            .map(|(account_id, remaining_quota)| JsonMinter {
+                account_id,
+                remaining_quota,
+            })
+            //since we turned the minters into an iterator, we need to turn it back into a vector to return
+            .collect()
+    }
+}
+
+impl Contract {
+    //make sure the predecessor is the owner of the contract
+    pub(crate) fn assert_contract_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the contract owner can call this method"
+        );
+    }
+
+    //make sure the predecessor is allowed to mint the given number of tokens without using up any of their quota.
+    //the owner can always mint, and anyone can mint while the contract is open
+    pub(crate) fn assert_can_mint(&self, token_count: u32) {
+        let minter_id = env::predecessor_account_id();
+        if self.open_mint || minter_id == self.owner_id {
+            return;
+        }
+
+        //the minter has to be approved. If they have a quota, it has to cover the tokens being minted
+        let quota = self.minters.get(&minter_id).expect("Not approved minter");
+        if let Some(remaining_quota) = quota {
+            assert!(
+                token_count <= remaining_quota,
+                "Minting {} tokens exceeds the remaining quota of {}",
+                token_count,
+                remaining_quota
+            );
+        }
+    }
+
+    //make sure the predecessor is allowed to mint the given number of tokens and use up that much of their quota
+    pub(crate) fn internal_use_mint_quota(&mut self, token_count: u32) {
+        self.assert_can_mint(token_count);
+
+        let minter_id = env::predecessor_account_id();
+        if self.open_mint || minter_id == self.owner_id {
+            return;
+        }
+        if let Some(Some(remaining_quota)) = self.minters.get(&minter_id) {
+            self.minters
+                .insert(&minter_id, &Some(remaining_quota - token_count));
+        }
+    }
+}
@@ -1,5 +1,10 @@
 use crate::*;
 
+//the most that all the perpetual royalties on a token can add up to, in basis points (100%)
+pub(crate) const MAX_TOTAL_ROYALTY: u32 = 10000;
+//the most receivers a token can have royalties for since we won't have enough GAS to pay out more people
+pub(crate) const MAX_ROYALTY_RECEIVERS: usize = 6;
+
 pub trait NonFungibleTokenCore {
     //calculates the payout for a token given the passed in balance. This is a view method
     fn nft_payout(&self, token_id: TokenId, balance: NearToken, max_len_payout: u32) -> Payout;
@@ -57,7 +62,7 @@ impl NonFungibleTokenCore for Contract {
         // payout to previous owner who gets 100% - total perpetual royalties
         payout_object.payout.insert(
             owner_id,
-            royalty_to_payout((10000 - total_perpetual).into(), balance),
+            royalty_to_payout(MAX_TOTAL_ROYALTY.saturating_sub(total_perpetual).into(), balance),
         );
 
         //return the payout object
@@ -123,10 +128,208 @@ impl NonFungibleTokenCore for Contract {
         // payout to previous owner who gets 100% - total perpetual royalties
         payout_object.payout.insert(
             owner_id,
-            royalty_to_payout((10000 - total_perpetual).into(), balance),
+            royalty_to_payout(MAX_TOTAL_ROYALTY.saturating_sub(total_perpetual).into(), balance),
         );
 
         //return the payout object
         payout_object
     }
 }
+
+#[near_bindgen]
+impl Contract {
+    //update the perpetual royalties for a token. Only the creator of the token can call this. Percentages can be
+    //lowered or a share can be moved to another receiver, but the total can never go up so owners never pay more
+    #[payable]
+    pub fn nft_update_royalty(
+        &mut self,
+        token_id: TokenId,
+        royalty: Vec<(AccountId, u32)>,
+        memo: Option<String>,
+    ) {
+        //assert at least one yocto for security reasons and to pay for any extra storage
+        assert_at_least_one_yocto();
+
+        //make sure the caller is the creator of the token
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token.creator_id,
+            "Only the creator can update the royalties"
+        );
+
+        //make sure the new royalties are valid and don't add up to more than the old ones
+        assert_valid_royalties(&royalty);
+        let old_total: u32 = token.royalty.values().sum();
+        let new_total: u32 = royalty.iter().map(|(_, amount)| amount).sum();
+        assert!(
+            new_total <= old_total,
+            "Royalties can only be lowered. The total is {} and the new total is {}",
+            old_total,
+            new_total
+        );
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        token.royalty = royalty.iter().cloned().collect();
+        self.tokens_by_id.insert(&token_id, &token);
+
+        //charge or refund the difference in storage
+        refund_storage_difference(initial_storage_usage);
+
+        // Construct the royalty update log.
+        let nft_royalty_update_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftRoyaltyUpdate(vec![NftRoyaltyUpdateLog {
+                // Vector of token IDs that were updated.
+                token_ids: vec![token_id],
+                // The new royalties for the tokens.
+                royalty: royalty
+                    .into_iter()
+                    .map(|(account_id, amount)| (account_id.to_string(), amount))
+                    .collect(),
+                // An optional memo to include.
+                memo,
+            }]),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_royalty_update_log.to_string());
+    }
+}
+
+//make sure the royalties for a token are valid. Every receiver can only be listed once and has to get more than 0 basis
+//points, and all the royalties can't add up to more than 100%
+pub(crate) fn assert_valid_royalties(royalty: &[(AccountId, u32)]) {
+    assert!(
+        royalty.len() <= MAX_ROYALTY_RECEIVERS,
+        "Cannot add more than {} perpetual royalty amounts",
+        MAX_ROYALTY_RECEIVERS
+    );
+
+    let mut total: u32 = 0;
+    for (index, (account_id, amount)) in royalty.iter().enumerate() {
+        assert!(
+            !royalty[..index].iter().any(|(other_id, _)| other_id == account_id),
+            "Royalty for {} is listed more than once",
+            account_id
+        );
+        assert!(*amount > 0, "Royalty for {} must be greater than 0", account_id);
+        total = total.saturating_add(*amount);
+    }
+    assert!(
+        total <= MAX_TOTAL_ROYALTY,
+        "Royalties cannot add up to more than {} basis points",
+        MAX_TOTAL_ROYALTY
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn royalty(entries: &[(&str, u32)]) -> Vec<(AccountId, u32)> {
+        entries
+            .iter()
+            .map(|(account_id, amount)| (account_id.parse().unwrap(), *amount))
+            .collect()
+    }
+
+    #[test]
+    fn valid_royalties() {
+        assert_valid_royalties(&royalty(&[]));
+        assert_valid_royalties(&royalty(&[("alice.near", 1)]));
+        //exactly 100% is allowed
+        assert_valid_royalties(&royalty(&[("alice.near", 4000), ("bob.near", 6000)]));
+        assert_valid_royalties(&royalty(&[
+            ("a.near", 100),
+            ("b.near", 100),
+            ("c.near", 100),
+            ("d.near", 100),
+            ("e.near", 100),
+            ("f.near", 100),
+        ]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalties cannot add up to more than 10000 basis points")]
+    fn royalties_over_100_percent() {
+        assert_valid_royalties(&royalty(&[("alice.near", 5000), ("bob.near", 5001)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalties cannot add up to more than 10000 basis points")]
+    fn royalties_that_overflow() {
+        assert_valid_royalties(&royalty(&[("alice.near", u32::MAX), ("bob.near", 1)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty for alice.near must be greater than 0")]
+    fn zero_royalty() {
+        assert_valid_royalties(&royalty(&[("alice.near", 0), ("bob.near", 500)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "Royalty for alice.near is listed more than once")]
+    fn duplicate_receiver() {
+        //in a JSON object the second entry would silently replace the first, so receivers are passed in as a list
+        let royalty: Vec<(AccountId, u32)> =
+            serde_json::from_str(r#"[["alice.near", 6000], ["bob.near", 1000], ["alice.near", 6000]]"#).unwrap();
+        assert_valid_royalties(&royalty);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot add more than 6 perpetual royalty amounts")]
+    fn too_many_receivers() {
+        assert_valid_royalties(&royalty(&[
+            ("a.near", 100),
+            ("b.near", 100),
+            ("c.near", 100),
+            ("d.near", 100),
+            ("e.near", 100),
+            ("f.near", 100),
+            ("g.near", 100),
+        ]));
+    }
+
+    #[test]
+    fn royalty_to_payout_rounds_down() {
+        //1 basis point of 9999 yocto is 0.9999 yocto which rounds down to 0
+        assert_eq!(
+            royalty_to_payout(1, NearToken::from_yoctonear(9999)),
+            NearToken::from_yoctonear(0)
+        );
+        //2.5% of 101 yocto is 2.525 yocto which rounds down to 2
+        assert_eq!(
+            royalty_to_payout(250, NearToken::from_yoctonear(101)),
+            NearToken::from_yoctonear(2)
+        );
+        //100% of the balance is the whole balance
+        assert_eq!(
+            royalty_to_payout(10000, NearToken::from_near(3)),
+            NearToken::from_near(3)
+        );
+        //0% of the balance is nothing
+        assert_eq!(
+            royalty_to_payout(0, NearToken::from_near(3)),
+            NearToken::from_yoctonear(0)
+        );
+    }
+
+    #[test]
+    fn royalty_to_payout_never_pays_more_than_the_balance() {
+        //the royalties and the owner's share are each rounded down so together they never add up to more than the balance
+        let balance = NearToken::from_yoctonear(10_001);
+        let shares = [3333, 3333, 3334];
+        let total = shares.iter().fold(NearToken::from_yoctonear(0), |total, share| {
+            total.saturating_add(royalty_to_payout(*share, balance))
+        });
+        assert!(total <= balance);
+        assert_eq!(total, NearToken::from_yoctonear(10_000));
+    }
+}
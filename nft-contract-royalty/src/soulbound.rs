@@ -0,0 +1,128 @@
+use crate::*;
+
+#[near_bindgen]
+impl Contract {
+    //check if a token can be transferred. Soulbound tokens return false
+    pub fn is_transferable(&self, token_id: TokenId) -> bool {
+        self.tokens_by_id
+            .get(&token_id)
+            .expect("No token")
+            .transferable
+    }
+
+    //get the account that a soulbound token's owner asked to move the token to, if there is one
+    pub fn get_pending_recovery(&self, token_id: TokenId) -> Option<AccountId> {
+        self.tokens_by_id
+            .get(&token_id)
+            .expect("No token")
+            .pending_recovery
+    }
+
+    //revoke a soulbound token by burning it. Only the creator of the token (the issuer) can call this
+    #[payable]
+    pub fn revoke_soulbound(&mut self, token_id: TokenId, memo: Option<String>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        //make sure the caller is the issuer of a soulbound token
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        let issuer_id = env::predecessor_account_id();
+        assert!(!token.transferable, "Token is not soulbound");
+        assert_eq!(issuer_id, token.creator_id, "Only the issuer can revoke the token");
+
+        //burn the token. The owner is refunded the released storage and the issuer is logged as the authorized account
+        self.internal_burn(&token_id, Some(issuer_id.to_string()), memo);
+    }
+
+    //ask the issuer to move a soulbound token to a new account, for example when the owner lost access to their keys
+    //on another device. Only the owner of the token can call this. Asking again replaces the previous request
+    #[payable]
+    pub fn request_recovery(&mut self, token_id: TokenId, new_owner_id: AccountId) {
+        //assert at least one yocto for security reasons and to pay for the storage of the request
+        assert_at_least_one_yocto();
+
+        //make sure the caller is the owner of a soulbound token
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        assert!(!token.transferable, "Token is not soulbound. Use nft_transfer instead");
+        assert_eq!(
+            env::predecessor_account_id(),
+            token.owner_id,
+            "Predecessor must be the token owner."
+        );
+        assert_ne!(
+            token.owner_id, new_owner_id,
+            "The token owner and the new owner should be different"
+        );
+
+        //measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        token.pending_recovery = Some(new_owner_id);
+        self.tokens_by_id.insert(&token_id, &token);
+
+        //charge or refund the difference in storage
+        refund_storage_difference(initial_storage_usage);
+    }
+
+    //cancel a pending recovery. This can be called by the owner to withdraw the request or by the issuer to reject it
+    #[payable]
+    pub fn cancel_recovery(&mut self, token_id: TokenId) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let mut token = self.tokens_by_id.get(&token_id).expect("No token");
+        let caller_id = env::predecessor_account_id();
+        assert!(
+            caller_id == token.owner_id || caller_id == token.creator_id,
+            "Only the owner or the issuer can cancel the recovery"
+        );
+        assert!(token.pending_recovery.is_some(), "No pending recovery");
+
+        //remove the request. The owner paid for its storage so they're refunded
+        let initial_storage_usage = env::storage_usage();
+        token.pending_recovery = None;
+        self.tokens_by_id.insert(&token_id, &token);
+        let released_storage = initial_storage_usage.saturating_sub(env::storage_usage());
+        let refund = env::storage_byte_cost().saturating_mul(released_storage.into());
+        if refund.gt(&ONE_YOCTONEAR) {
+            Promise::new(token.owner_id).transfer(refund);
+        }
+    }
+
+    //approve a pending recovery and move the soulbound token to the account the owner asked for.
+    //Only the creator of the token (the issuer) can call this
+    #[payable]
+    pub fn approve_recovery(&mut self, token_id: TokenId, memo: Option<String>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+
+        let token = self.tokens_by_id.get(&token_id).expect("No token");
+        let issuer_id = env::predecessor_account_id();
+        assert_eq!(issuer_id, token.creator_id, "Only the issuer can approve the recovery");
+        let new_owner_id = token.pending_recovery.clone().expect("No pending recovery");
+        //locked tokens can't be moved until they're unlocked or the lock ends
+        assert_not_locked(&token);
+
+        //move the token to the new owner. This clears the request and logs the transfer with the issuer as the authorized account
+        let previous_token = self.internal_move_token(
+            token,
+            &new_owner_id,
+            &token_id,
+            Some(issuer_id.to_string()),
+            memo,
+        );
+
+        //soulbound tokens can't have approvals, so the owner is only refunded the storage used by the request
+        let refund = env::storage_byte_cost()
+            .saturating_mul(bytes_for_pending_recovery(&new_owner_id));
+        if refund.gt(&ONE_YOCTONEAR) {
+            Promise::new(previous_token.owner_id).transfer(refund);
+        }
+    }
+}
+
+//calculate how many bytes a pending recovery is taking up
+pub(crate) fn bytes_for_pending_recovery(account_id: &AccountId) -> u128 {
+    // The extra 4 bytes are coming from Borsh serialization to store the length of the string.
+    account_id.as_str().len() as u128 + 4
+}
@@ -0,0 +1,160 @@
+use crate::*;
+use near_sdk::Gas;
+
+//GAS that has to be left before burning another expired token. This covers burning the token, logging the
+//burn event and refunding the owner. Once less is left, the tokens that haven't been looked at are skipped
+const GAS_FOR_BURN_EXPIRED_TOKEN: Gas = Gas::from_tgas(5);
+
+impl TokenMetadata {
+    //whether the token has expired at the given time
+    pub(crate) fn is_expired(&self, now: u64) -> bool {
+        self.expires_at.map_or(false, |expires_at| now >= expires_at)
+    }
+
+    //whether the token is valid at the given time. A token is valid once it starts and until it expires
+    pub(crate) fn is_valid(&self, now: u64) -> bool {
+        self.starts_at.map_or(true, |starts_at| now >= starts_at) && !self.is_expired(now)
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    //turn on (true) or off (false) the policy that expired tokens can't be transferred or approved.
+    //Only the contract owner can call this
+    pub fn set_enforce_validity(&mut self, enforce_validity: bool) {
+        self.assert_contract_owner();
+        self.enforce_validity = enforce_validity;
+    }
+
+    //check if expired tokens can't be transferred or approved
+    pub fn is_validity_enforced(&self) -> bool {
+        self.enforce_validity
+    }
+
+    //burn the passed in tokens that have expired. Anyone can call this while the validity policy is on. Tokens
+    //that don't exist, haven't expired or are locked are skipped. The owners are refunded the storage that is
+    //released. If the GAS runs low, the rest of the tokens are left for another call.
+    //returns the IDs of the tokens that were burned
+    pub fn nft_burn_expired(&mut self, token_ids: Vec<TokenId>) -> Vec<TokenId> {
+        //expired tokens are only cleaned up if the contract owner chose to enforce validity
+        assert!(self.enforce_validity, "Validity is not enforced");
+
+        let now = env::block_timestamp_ms();
+        //the caller is logged as the account that burned the tokens
+        let caller_id = env::predecessor_account_id().to_string();
+
+        let mut burned = Vec::new();
+        for token_id in token_ids {
+            //stop once there isn't enough GAS left to burn another token. The caller can see which tokens
+            //were burned from the return value and pass in the rest again
+            let remaining_gas = env::prepaid_gas().saturating_sub(env::used_gas());
+            if remaining_gas < GAS_FOR_BURN_EXPIRED_TOKEN {
+                break;
+            }
+
+            //skip tokens that don't exist (they may have been burned already) or haven't expired
+            let is_expired = self
+                .token_metadata_by_id
+                .get(&token_id)
+                .map_or(false, |metadata| metadata.is_expired(now));
+            if !is_expired {
+                continue;
+            }
+
+            //skip tokens that are locked to an account
+            let token = self.tokens_by_id.get(&token_id).unwrap();
+            if token.lock.map_or(false, |lock| lock.is_active(now)) {
+                continue;
+            }
+
+            self.internal_burn(&token_id, Some(caller_id.clone()), Some("expired".to_string()));
+            burned.push(token_id);
+        }
+
+        burned
+    }
+}
+
+impl Contract {
+    //if the validity policy is on, make sure the token hasn't expired
+    pub(crate) fn assert_not_expired(&self, token_id: &TokenId) {
+        if !self.enforce_validity {
+            return;
+        }
+
+        let metadata = self.token_metadata_by_id.get(token_id).expect("No token");
+        assert!(
+            !metadata.is_expired(env::block_timestamp_ms()),
+            "Token has expired"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    fn token_metadata(expires_at: Option<u64>) -> TokenMetadata {
+        TokenMetadata {
+            title: None,
+            description: None,
+            media: None,
+            media_hash: None,
+            copies: None,
+            issued_at: None,
+            expires_at,
+            starts_at: None,
+            updated_at: None,
+            extra: None,
+            reference: None,
+            reference_hash: None,
+        }
+    }
+
+    //a contract owned by accounts(1) with token "1" that expired and token "2" that never expires
+    fn setup() -> (VMContextBuilder, Contract) {
+        let mut context = VMContextBuilder::new();
+        context
+            .predecessor_account_id(accounts(1))
+            .block_timestamp(2_000_000_000);
+        testing_env!(context.build());
+        let mut contract = Contract::new_default_meta(accounts(1));
+        contract.internal_mint("1".to_string(), accounts(2), token_metadata(Some(1)), None, true);
+        contract.internal_mint("2".to_string(), accounts(2), token_metadata(None), None, true);
+        (context, contract)
+    }
+
+    #[test]
+    fn burn_expired_tokens() {
+        let (_, mut contract) = setup();
+        contract.set_enforce_validity(true);
+
+        let burned = contract.nft_burn_expired(vec!["1".to_string(), "2".to_string(), "3".to_string()]);
+
+        assert_eq!(burned, vec!["1".to_string()]);
+        assert!(contract.tokens_by_id.get(&"1".to_string()).is_none());
+        assert!(contract.tokens_by_id.get(&"2".to_string()).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Validity is not enforced")]
+    fn burn_expired_needs_validity_enforced() {
+        let (_, mut contract) = setup();
+        contract.nft_burn_expired(vec!["1".to_string()]);
+    }
+
+    #[test]
+    fn burn_expired_stops_when_gas_runs_low() {
+        let (mut context, mut contract) = setup();
+        contract.set_enforce_validity(true);
+
+        //not enough GAS is left to burn a single token so every token is left for another call
+        testing_env!(context.prepaid_gas(Gas::from_tgas(4)).build());
+        let burned = contract.nft_burn_expired(vec!["1".to_string()]);
+
+        assert!(burned.is_empty());
+        assert!(contract.tokens_by_id.get(&"1".to_string()).is_some());
+    }
+}
@@ -13,6 +13,8 @@ pub struct JsonSeries {
     royalty: Option<HashMap<AccountId, u32>>,
     // Owner of the collection
     owner_id: AccountId,
+    // How the revenue from minting priced tokens is split, in basis points. Whatever isn't split goes to the owner
+    primary_split: Option<HashMap<AccountId, u32>>,
 }
 
 #[near_bindgen]
@@ -122,6 +124,7 @@ impl Contract {
                 metadata: series.metadata,
                 royalty: series.royalty,
                 owner_id: series.owner_id,
+                primary_split: series.primary_split,
             })
         } else {
             //if there isn't a series, we'll return None
@@ -3,7 +3,7 @@ use std::fmt;
 use near_sdk::serde::{Deserialize, Serialize};
 
 /// Enum that represents the data type of the EventLog.
-/// The enum can either be an NftMint or an NftTransfer.
+/// The enum can either be an NftMint, an NftTransfer or an NftPrimarySale.
 #[derive(Serialize, Deserialize, Debug)]
 #[serde(tag = "event", content = "data")]
 #[serde(rename_all = "snake_case")]
@@ -12,6 +12,7 @@ use near_sdk::serde::{Deserialize, Serialize};
 pub enum EventLogVariant {
     NftMint(Vec<NftMintLog>),
     NftTransfer(Vec<NftTransferLog>),
+    NftPrimarySale(Vec<NftPrimarySaleLog>),
 }
 
 /// Interface to capture data about an event
@@ -78,6 +79,22 @@ pub struct NftTransferLog {
     pub memo: Option<String>,
 }
 
+/// An event log to capture how the revenue from minting a priced series token was distributed
+///
+/// Arguments
+/// * `series_id`: the series the token was minted from
+/// * `token_id`: "1:1"
+/// * `receiver_id`: account that was paid
+/// * `amount`: amount in yoctoNEAR that was paid
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftPrimarySaleLog {
+    pub series_id: u64,
+    pub token_id: String,
+    pub receiver_id: String,
+    pub amount: String,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -135,4 +152,28 @@ mod tests {
         };
         assert_eq!(expected, log.to_string());
     }
+
+    #[test]
+    fn nep_format_primary_sale() {
+        let expected = r#"EVENT_JSON:{"standard":"nep171","version":"1.0.0","event":"nft_primary_sale","data":[{"series_id":1,"token_id":"1:1","receiver_id":"artist.near","amount":"900"},{"series_id":1,"token_id":"1:1","receiver_id":"charity.near","amount":"100"}]}"#;
+        let log = EventLog {
+            standard: "nep171".to_string(),
+            version: "1.0.0".to_string(),
+            event: EventLogVariant::NftPrimarySale(vec![
+                NftPrimarySaleLog {
+                    series_id: 1,
+                    token_id: "1:1".to_string(),
+                    receiver_id: "artist.near".to_string(),
+                    amount: "900".to_string(),
+                },
+                NftPrimarySaleLog {
+                    series_id: 1,
+                    token_id: "1:1".to_string(),
+                    receiver_id: "charity.near".to_string(),
+                    amount: "100".to_string(),
+                },
+            ]),
+        };
+        assert_eq!(expected, log.to_string());
+    }
 }
@@ -6,9 +6,11 @@ use std::mem::size_of;
 const ZERO_NEAR: NearToken = NearToken::from_yoctonear(0);
 const ONE_YOCTONEAR: NearToken = NearToken::from_yoctonear(1);
 
-//convert the royalty percentage and amount to pay into a payout
+//convert the royalty in basis points and amount to pay into a payout
 pub(crate) fn royalty_to_payout(royalty_percentage: u128, amount_to_pay: NearToken) -> NearToken {
-    amount_to_pay.saturating_mul(royalty_percentage)
+    amount_to_pay
+        .saturating_mul(royalty_percentage)
+        .saturating_div(10000)
 }
 
 //calculate how many bytes the account ID is taking up
@@ -68,11 +70,14 @@ pub(crate) fn assert_at_least_one_yocto() {
     )
 }
 
-// Send all the non storage funds to the series owner
-pub(crate) fn payout_series_owner(
+// Split all the non storage funds between the primary split receivers and the series owner
+pub(crate) fn payout_primary_sale(
     storage_used: u128,
     price_per_token: NearToken,
+    series_id: SeriesId,
+    token_id: &TokenId,
     owner_id: AccountId,
+    primary_split: Option<&HashMap<AccountId, u32>>,
 ) {
     //get how much it would cost to store the information
     let required_cost = env::storage_byte_cost().saturating_mul(storage_used);
@@ -87,9 +92,36 @@ pub(crate) fn payout_series_owner(
         price_per_token
     );
 
-    // If there's a price for the token, transfer everything but the storage to the series owner
+    // If there's a price for the token, split everything but the storage. The series owner gets whatever isn't split
     if price_per_token.gt(&ZERO_NEAR) {
-        Promise::new(owner_id).transfer(attached_deposit.saturating_sub(required_cost));
+        let revenue = attached_deposit.saturating_sub(required_cost);
+        let distribution = royalty_payout(primary_split, &owner_id, revenue, u32::MAX);
+
+        let mut primary_sale_logs = Vec::new();
+        for (receiver_id, amount) in distribution.payout {
+            if amount.gt(&ZERO_NEAR) {
+                primary_sale_logs.push(NftPrimarySaleLog {
+                    series_id,
+                    token_id: token_id.to_string(),
+                    receiver_id: receiver_id.to_string(),
+                    amount: amount.as_yoctonear().to_string(),
+                });
+                Promise::new(receiver_id).transfer(amount);
+            }
+        }
+
+        // Construct the primary sale log with one entry for every distribution.
+        let nft_primary_sale_log: EventLog = EventLog {
+            // Standard name ("nep171").
+            standard: NFT_STANDARD_NAME.to_string(),
+            // Version of the standard ("nft-1.0.0").
+            version: NFT_METADATA_SPEC.to_string(),
+            // The data related with the event stored in a vector.
+            event: EventLogVariant::NftPrimarySale(primary_sale_logs),
+        };
+
+        // Log the serialized json.
+        env::log_str(&nft_primary_sale_log.to_string());
     }
 }
 
@@ -22,6 +22,7 @@ mod enumeration;
 mod events;
 mod internal;
 mod metadata;
+mod migrate;
 mod nft_core;
 mod owner;
 mod royalty;
@@ -47,6 +48,8 @@ pub struct Series {
     price: Option<NearToken>,
     // Owner of the collection
     owner_id: AccountId,
+    // How the revenue from minting priced tokens is split, in basis points. Whatever isn't split goes to the owner
+    primary_split: Option<HashMap<AccountId, u32>>,
 }
 
 pub type SeriesId = u64;
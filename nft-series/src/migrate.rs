@@ -0,0 +1,146 @@
+use crate::*;
+
+//the state of the contract before series could split their primary sales
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldContract {
+    pub owner_id: AccountId,
+    pub approved_minters: LookupSet<AccountId>,
+    pub approved_creators: LookupSet<AccountId>,
+    pub series_by_id: UnorderedMap<SeriesId, OldSeries>,
+    pub tokens_by_id: UnorderedMap<TokenId, Token>,
+    pub tokens_per_owner: LookupMap<AccountId, UnorderedSet<TokenId>>,
+    pub metadata: LazyOption<NFTContractMetadata>,
+}
+
+//a series as it was stored before it had a primary sale split
+#[derive(BorshDeserialize, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct OldSeries {
+    pub metadata: TokenMetadata,
+    pub royalty: Option<HashMap<AccountId, u32>>,
+    pub tokens: UnorderedSet<TokenId>,
+    pub price: Option<NearToken>,
+    pub owner_id: AccountId,
+}
+
+#[near_bindgen]
+impl Contract {
+    /*
+        migrate the series to the layout with a primary sale split. Every series is read in the old layout and
+        written again without a split, so all of the revenue from minting keeps going to the series owner. Old
+        royalties were stored as percentages, so they're multiplied by 100 to turn them into basis points. The
+        tokens of each series are left where they are. This touches every series so the GAS it needs grows with
+        the number of series on the contract.
+    */
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let mut old_state: OldContract = env::state_read().expect("Failed to read the old state");
+
+        //take every series out of the old map
+        let old_series = old_state.series_by_id.to_vec();
+        old_state.series_by_id.clear();
+
+        //the old map is empty so the new one can use the same prefix
+        let mut series_by_id = UnorderedMap::new(StorageKey::SeriesById);
+        for (series_id, series) in old_series {
+            series_by_id.insert(
+                &series_id,
+                &Series {
+                    metadata: series.metadata,
+                    royalty: series.royalty.map(|royalty| {
+                        royalty
+                            .into_iter()
+                            .map(|(account_id, percent)| (account_id, percent.saturating_mul(100)))
+                            .collect()
+                    }),
+                    tokens: series.tokens,
+                    price: series.price,
+                    owner_id: series.owner_id,
+                    primary_split: None,
+                },
+            );
+        }
+
+        Self {
+            owner_id: old_state.owner_id,
+            approved_minters: old_state.approved_minters,
+            approved_creators: old_state.approved_creators,
+            series_by_id,
+            tokens_by_id: old_state.tokens_by_id,
+            tokens_per_owner: old_state.tokens_per_owner,
+            metadata: old_state.metadata,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    #[test]
+    fn migrate_series_with_royalties_to_basis_points() {
+        let mut context = VMContextBuilder::new();
+        context.predecessor_account_id(accounts(0));
+        testing_env!(context.build());
+
+        //write the state the way the old contract stored it, with a priced series that has royalties in percent and
+        //has minted a token
+        let mut tokens = UnorderedSet::new(StorageKey::SeriesByIdInner {
+            account_id_hash: hash_account_id(&format!("{}.{}", 1, accounts(1))),
+        });
+        tokens.insert(&"1:1".to_string());
+        let mut series_by_id = UnorderedMap::new(StorageKey::SeriesById);
+        series_by_id.insert(
+            &1,
+            &OldSeries {
+                metadata: TokenMetadata {
+                    title: Some("Series".to_string()),
+                    description: None,
+                    media: None,
+                    media_hash: None,
+                    copies: None,
+                    issued_at: None,
+                    expires_at: None,
+                    starts_at: None,
+                    updated_at: None,
+                    extra: None,
+                    reference: None,
+                    reference_hash: None,
+                },
+                royalty: Some(HashMap::from([(accounts(2), 10), (accounts(3), 5)])),
+                tokens,
+                price: Some(NearToken::from_near(1)),
+                owner_id: accounts(1),
+            },
+        );
+        let old_state = OldContract {
+            owner_id: accounts(0),
+            approved_minters: LookupSet::new(StorageKey::ApprovedMinters),
+            approved_creators: LookupSet::new(StorageKey::ApprovedCreators),
+            series_by_id,
+            tokens_by_id: UnorderedMap::new(StorageKey::TokensById),
+            tokens_per_owner: LookupMap::new(StorageKey::TokensPerOwner),
+            metadata: LazyOption::new(StorageKey::NFTContractMetadata, None),
+        };
+        env::state_write(&old_state);
+
+        let contract = Contract::migrate();
+
+        //the series keeps its price, owner and tokens, its royalties are in basis points and it doesn't split its
+        //primary sales
+        let series = contract.series_by_id.get(&1).unwrap();
+        assert_eq!(series.price, Some(NearToken::from_near(1)));
+        assert_eq!(series.owner_id, accounts(1));
+        assert_eq!(series.tokens.to_vec(), vec!["1:1".to_string()]);
+        assert_eq!(
+            series.royalty,
+            Some(HashMap::from([(accounts(2), 1000), (accounts(3), 500)]))
+        );
+        assert!(series.primary_split.is_none());
+        assert_eq!(contract.series_by_id.len(), 1);
+    }
+}
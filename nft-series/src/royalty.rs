@@ -23,58 +23,14 @@ impl NonFungibleTokenCore for Contract {
         //get the token object
         let token = self.tokens_by_id.get(&token_id).expect("No token");
 
-        //get the owner of the token
-        let owner_id = token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
-        //keep track of the payout object to send back
-        let mut payout_object = Payout {
-            payout: HashMap::new(),
-        };
         //get the royalty object from series
         let cur_series = self
             .series_by_id
             .get(&token.series_id)
             .expect("Not a series");
 
-        // If the series doesn't have a royalty, we'll return an a payout object that just includes the owner
-        let royalty_option = cur_series.royalty;
-        if royalty_option.is_none() {
-            let mut payout = HashMap::new();
-            payout.insert(owner_id, balance);
-            return Payout { payout: payout };
-        }
-        // Otherwise, we will get the royalty object from the series
-        let royalty = royalty_option.unwrap();
-
-        //make sure we're not paying out to too many people (GAS limits this)
-        assert!(
-            royalty.len() as u32 <= max_len_payout,
-            "Market can&not payout to that many receivers"
-        );
-
-        //go through each key and value in the royalty object
-        for (k, v) in royalty.iter() {
-            //get the key
-            let key = k.clone();
-            //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-            if key != owner_id {
-                //
-                payout_object
-                    .payout
-                    .insert(key, royalty_to_payout(*v as u128, balance));
-                total_perpetual += *v;
-            }
-        }
-
-        // payout to previous owner who gets 100% - total perpetual royalties
-        payout_object.payout.insert(
-            owner_id,
-            royalty_to_payout((10000 - total_perpetual).into(), balance),
-        );
-
-        //return the payout object
-        payout_object
+        //split the balance between the royalty receivers and the owner
+        royalty_payout(cur_series.royalty.as_ref(), &token.owner_id, balance, max_len_payout)
     }
 
     //transfers the token to the receiver ID and returns the payout object that should be payed given the passed in balance.
@@ -102,31 +58,142 @@ impl NonFungibleTokenCore for Contract {
             &previous_token.approved_account_ids,
         );
 
-        //get the owner of the token
-        let owner_id = previous_token.owner_id;
-        //keep track of the total perpetual royalties
-        let mut total_perpetual = 0;
-        //keep track of the payout object to send back
-        let mut payout_object = Payout {
-            payout: HashMap::new(),
-        };
-
         //get the royalty object from series
         let cur_series = self
             .series_by_id
             .get(&previous_token.series_id)
             .expect("Not a series");
 
-        // If the series doesn't have a royalty, we'll return an a payout object that just includes the owner
-        let royalty_option = cur_series.royalty;
-        if royalty_option.is_none() {
-            let mut payout = HashMap::new();
-            payout.insert(owner_id, balance);
-            return Payout { payout: payout };
+        //split the balance between the royalty receivers and the previous owner
+        royalty_payout(
+            cur_series.royalty.as_ref(),
+            &previous_token.owner_id,
+            balance,
+            max_len_payout,
+        )
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Update the royalties for a series. Only the owner of the series can call this. Royalties can be lowered or
+    /// moved to other receivers but the total can never go up, so token owners never pay more than when they bought in.
+    /// Caller must attach enough $NEAR to cover any extra storage.
+    #[payable]
+    pub fn update_series_royalty(&mut self, id: U64, royalty: Option<HashMap<AccountId, u32>>) {
+        //assert at least one yocto for security reasons and to pay for any extra storage
+        assert_at_least_one_yocto();
+
+        // Get the series and ensure the caller is its owner
+        let mut series = self.series_by_id.get(&id.0).expect("Not a series");
+        require!(
+            env::predecessor_account_id() == series.owner_id,
+            "only the series owner can update the royalty"
+        );
+
+        // Ensure the new royalties are valid and don't add up to more than the current ones
+        if let Some(royalty) = &royalty {
+            assert_valid_royalties(royalty);
+        }
+        let old_total = total_royalty(series.royalty.as_ref());
+        let new_total = total_royalty(royalty.as_ref());
+        require!(
+            new_total <= old_total,
+            format!(
+                "royalty can only be lowered. The total is {} and the new total is {}",
+                old_total, new_total
+            )
+        );
+
+        // Measure the initial storage being used on the contract
+        let initial_storage_usage = env::storage_usage();
+
+        series.royalty = royalty;
+        self.series_by_id.insert(&id.0, &series);
+
+        // Charge for any extra storage, or refund the storage that was released along with the deposit
+        let storage_usage = env::storage_usage();
+        if storage_usage > initial_storage_usage {
+            refund_deposit((storage_usage - initial_storage_usage).into());
+        } else {
+            let released_storage = env::storage_byte_cost()
+                .saturating_mul((initial_storage_usage - storage_usage).into());
+            Promise::new(env::predecessor_account_id())
+                .transfer(released_storage.saturating_add(env::attached_deposit()));
         }
-        // Otherwise, we will get the royalty object from the series
-        let royalty = royalty_option.unwrap();
+    }
+}
+
+//the most receivers a series can have royalties for since we won't have enough GAS to pay out more people
+pub(crate) const MAX_ROYALTY_RECEIVERS: usize = 6;
+//the most that all the royalties on a series can add up to, in basis points (100%)
+pub(crate) const MAX_TOTAL_ROYALTY: u32 = 10000;
+
+//make sure the royalties for a series are valid. Every receiver has to get more than 0 basis points and all
+//the royalties can't add up to more than 100%
+pub(crate) fn assert_valid_royalties(royalty: &HashMap<AccountId, u32>) {
+    require!(
+        royalty.len() <= MAX_ROYALTY_RECEIVERS,
+        format!("can't have more than {} royalty receivers", MAX_ROYALTY_RECEIVERS)
+    );
+    require!(
+        royalty.values().all(|amount| *amount > 0),
+        "every royalty must be greater than 0"
+    );
+    require!(
+        total_royalty(Some(royalty)) <= MAX_TOTAL_ROYALTY,
+        "total royalty can't exceed 10000 basis points"
+    );
+}
+
+//the most receivers the revenue from minting a token can be split between
+pub(crate) const MAX_PRIMARY_SPLIT_RECEIVERS: usize = 10;
+
+//make sure the primary sale split for a series is valid. Every receiver has to get more than 0 basis points and
+//the split can't add up to more than 100%
+pub(crate) fn assert_valid_primary_split(primary_split: &HashMap<AccountId, u32>) {
+    require!(
+        primary_split.len() <= MAX_PRIMARY_SPLIT_RECEIVERS,
+        format!(
+            "can't split primary sales between more than {} receivers",
+            MAX_PRIMARY_SPLIT_RECEIVERS
+        )
+    );
+    require!(
+        primary_split.values().all(|amount| *amount > 0),
+        "every primary split share must be greater than 0"
+    );
+    require!(
+        total_royalty(Some(primary_split)) <= MAX_TOTAL_ROYALTY,
+        "total primary split can't exceed 10000 basis points"
+    );
+}
+
+//add up the royalties in basis points
+pub(crate) fn total_royalty(royalty: Option<&HashMap<AccountId, u32>>) -> u32 {
+    royalty.map_or(0, |royalty| {
+        royalty
+            .values()
+            .fold(0u32, |total, amount| total.saturating_add(*amount))
+    })
+}
 
+//split the balance between the royalty receivers and the owner. Each receiver gets their basis points of the balance
+//rounded down, and the owner gets everything else so the payout always adds up to exactly the balance
+pub(crate) fn royalty_payout(
+    royalty: Option<&HashMap<AccountId, u32>>,
+    owner_id: &AccountId,
+    balance: NearToken,
+    max_len_payout: u32,
+) -> Payout {
+    //keep track of the payout object to send back
+    let mut payout_object = Payout {
+        payout: HashMap::new(),
+    };
+    //keep track of how much of the balance is paid out in royalties
+    let mut total_perpetual = NearToken::from_yoctonear(0);
+
+    if let Some(royalty) = royalty {
         //make sure we're not paying out to too many people (GAS limits this)
         assert!(
             royalty.len() as u32 <= max_len_payout,
@@ -135,25 +202,162 @@ impl NonFungibleTokenCore for Contract {
 
         //go through each key and value in the royalty object
         for (k, v) in royalty.iter() {
-            //get the key
-            let key = k.clone();
             //only insert into the payout if the key isn't the token owner (we add their payout at the end)
-            if key != owner_id {
-                //
-                payout_object
-                    .payout
-                    .insert(key, royalty_to_payout(*v as u128, balance));
-                total_perpetual += *v;
+            if k != owner_id {
+                let amount = royalty_to_payout(*v as u128, balance);
+                payout_object.payout.insert(k.clone(), amount);
+                total_perpetual = total_perpetual.saturating_add(amount);
             }
         }
+    }
+
+    // payout to the owner who gets the balance minus all the royalties, including any rounding dust
+    payout_object
+        .payout
+        .insert(owner_id.clone(), balance.saturating_sub(total_perpetual));
+
+    //return the payout object
+    payout_object
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn account(account_id: &str) -> AccountId {
+        account_id.parse().unwrap()
+    }
+
+    fn royalty(entries: &[(&str, u32)]) -> HashMap<AccountId, u32> {
+        entries
+            .iter()
+            .map(|(account_id, amount)| (account(account_id), *amount))
+            .collect()
+    }
 
-        // payout to previous owner who gets 100% - total perpetual royalties
-        payout_object.payout.insert(
-            owner_id,
-            royalty_to_payout((10000 - total_perpetual).into(), balance),
+    fn payout_total(payout: &Payout) -> NearToken {
+        payout
+            .payout
+            .values()
+            .fold(NearToken::from_yoctonear(0), |total, amount| total.saturating_add(*amount))
+    }
+
+    #[test]
+    fn royalty_to_payout_uses_basis_points() {
+        //5% of 1 NEAR
+        assert_eq!(
+            royalty_to_payout(500, NearToken::from_near(1)),
+            NearToken::from_millinear(50)
+        );
+        //100% of 1 NEAR
+        assert_eq!(
+            royalty_to_payout(10000, NearToken::from_near(1)),
+            NearToken::from_near(1)
         );
+    }
+
+    #[test]
+    fn payout_sums_to_balance() {
+        let royalty = royalty(&[("artist.near", 1000), ("platform.near", 250)]);
+        let balance = NearToken::from_near(7);
+        let payout = royalty_payout(Some(&royalty), &account("owner.near"), balance, 10);
+
+        assert_eq!(payout_total(&payout), balance);
+        assert_eq!(payout.payout[&account("artist.near")], NearToken::from_millinear(700));
+        assert_eq!(payout.payout[&account("platform.near")], NearToken::from_millinear(175));
+        assert_eq!(payout.payout[&account("owner.near")], NearToken::from_millinear(6125));
+    }
+
+    #[test]
+    fn payout_gives_rounding_dust_to_owner() {
+        //every royalty rounds down, so the owner gets the dust and the payout still adds up to the balance
+        let royalty = royalty(&[("a.near", 3333), ("b.near", 3333), ("c.near", 3333)]);
+        for balance in [1, 7, 9_999, 10_001, 123_456_789] {
+            let balance = NearToken::from_yoctonear(balance);
+            let payout = royalty_payout(Some(&royalty), &account("owner.near"), balance, 10);
+            assert_eq!(payout_total(&payout), balance);
+        }
+
+        let payout = royalty_payout(
+            Some(&royalty),
+            &account("owner.near"),
+            NearToken::from_yoctonear(10_001),
+            10,
+        );
+        assert_eq!(payout.payout[&account("a.near")], NearToken::from_yoctonear(3_333));
+        assert_eq!(payout.payout[&account("owner.near")], NearToken::from_yoctonear(2));
+    }
+
+    #[test]
+    fn payout_without_royalty_goes_to_owner() {
+        let balance = NearToken::from_near(3);
+        let payout = royalty_payout(None, &account("owner.near"), balance, 0);
+        assert_eq!(payout.payout.len(), 1);
+        assert_eq!(payout.payout[&account("owner.near")], balance);
+    }
+
+    #[test]
+    fn payout_at_100_percent_royalty() {
+        let royalty = royalty(&[("artist.near", 10000)]);
+        let balance = NearToken::from_near(2);
+        let payout = royalty_payout(Some(&royalty), &account("owner.near"), balance, 10);
+        assert_eq!(payout.payout[&account("artist.near")], balance);
+        assert_eq!(payout.payout[&account("owner.near")], NearToken::from_yoctonear(0));
+        assert_eq!(payout_total(&payout), balance);
+    }
+
+    #[test]
+    fn payout_when_owner_is_a_royalty_receiver() {
+        //the owner's own royalty is folded into their share
+        let royalty = royalty(&[("owner.near", 1000), ("artist.near", 500)]);
+        let balance = NearToken::from_near(1);
+        let payout = royalty_payout(Some(&royalty), &account("owner.near"), balance, 10);
+        assert_eq!(payout.payout.len(), 2);
+        assert_eq!(payout.payout[&account("owner.near")], NearToken::from_millinear(950));
+        assert_eq!(payout_total(&payout), balance);
+    }
+
+    #[test]
+    #[should_panic(expected = "Market cannot payout to that many receivers")]
+    fn payout_too_many_receivers() {
+        let royalty = royalty(&[("a.near", 100), ("b.near", 100)]);
+        royalty_payout(Some(&royalty), &account("owner.near"), NearToken::from_near(1), 1);
+    }
+
+    #[test]
+    fn valid_royalties() {
+        assert_valid_royalties(&royalty(&[("artist.near", 4000), ("platform.near", 6000)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "total royalty can't exceed 10000 basis points")]
+    fn royalties_over_100_percent() {
+        assert_valid_royalties(&royalty(&[("artist.near", 9000), ("platform.near", 1001)]));
+    }
+
+    #[test]
+    #[should_panic(expected = "every royalty must be greater than 0")]
+    fn zero_royalty() {
+        assert_valid_royalties(&royalty(&[("artist.near", 0)]));
+    }
+
+    #[test]
+    fn primary_split_leaves_the_rest_to_the_series_owner() {
+        //the primary split uses the same math as royalties so the owner gets whatever isn't split
+        let primary_split = royalty(&[("platform.near", 1500), ("charity.near", 500)]);
+        assert_valid_primary_split(&primary_split);
+
+        let revenue = NearToken::from_near(2);
+        let payout = royalty_payout(Some(&primary_split), &account("artist.near"), revenue, u32::MAX);
+        assert_eq!(payout.payout[&account("platform.near")], NearToken::from_millinear(300));
+        assert_eq!(payout.payout[&account("charity.near")], NearToken::from_millinear(100));
+        assert_eq!(payout.payout[&account("artist.near")], NearToken::from_millinear(1600));
+        assert_eq!(payout_total(&payout), revenue);
+    }
 
-        //return the payout object
-        payout_object
+    #[test]
+    #[should_panic(expected = "total primary split can't exceed 10000 basis points")]
+    fn primary_split_over_100_percent() {
+        assert_valid_primary_split(&royalty(&[("platform.near", 5000), ("charity.near", 5001)]));
     }
 }
@@ -14,6 +14,7 @@ impl Contract {
         metadata: TokenMetadata,
         royalty: Option<HashMap<AccountId, u32>>,
         price: Option<NearToken>,
+        primary_split: Option<HashMap<AccountId, u32>>,
     ) {
         // Measure the initial storage being used on the contract
         let initial_storage_usage = env::storage_usage();
@@ -25,14 +26,14 @@ impl Contract {
             "only approved creators can add a type"
         );
 
-        // Check that the total royalty amount does not exceed 100%
-        if !royalty.is_none() {
-            let mut total_royalty = 0;
+        // Check that the royalties are in basis points and the total does not exceed 100% (10000)
+        if let Some(royalty) = &royalty {
+            assert_valid_royalties(royalty);
+        }
 
-            for (_, v) in royalty.clone().unwrap().iter() {
-                total_royalty += *v;
-            }
-            require!(total_royalty <= 100, "total royalty can't exceed 100%");
+        // Check that the primary sale split is in basis points and the total does not exceed 100% (10000)
+        if let Some(primary_split) = &primary_split {
+            assert_valid_primary_split(primary_split);
         }
 
         // Insert the series and ensure it doesn't already exist
@@ -49,6 +50,7 @@ impl Contract {
                         }),
                         owner_id: caller,
                         price: price.map(|p| p),
+                        primary_split,
                     }
                 )
                 .is_none(),
@@ -148,12 +150,16 @@ impl Contract {
         //calculate the required storage which was the used - initial
         let required_storage_in_bytes = env::storage_usage() - initial_storage_usage;
 
-        // If there's some price for the token, we'll payout the series owner. Otherwise, refund the excess deposit for storage to the caller
+        // If there's some price for the token, we'll split the revenue between the series owner and the primary split receivers.
+        // Otherwise, refund the excess deposit for storage to the caller
         if price_per_token.gt(&NearToken::from_yoctonear(0)) {
-            payout_series_owner(
+            payout_primary_sale(
                 required_storage_in_bytes.into(),
                 price_per_token,
+                id.0,
+                &token_id,
                 series.owner_id,
+                series.primary_split.as_ref(),
             );
         } else {
             refund_deposit(required_storage_in_bytes.into());
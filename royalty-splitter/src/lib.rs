@@ -0,0 +1,241 @@
+use near_sdk::borsh::{BorshDeserialize, BorshSerialize};
+use near_sdk::collections::UnorderedMap;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{
+    assert_one_yocto, env, ext_contract, log, near_bindgen, AccountId, BorshStorageKey, Gas,
+    NearSchema, NearToken, PanicOnDefault, Promise,
+};
+use std::collections::HashMap;
+
+//GAS to attach when registering the splitter with a market
+const GAS_FOR_REGISTER: Gas = Gas::from_tgas(10);
+
+//the maximum number of members the royalties can be split between (every member is paid with its own transfer)
+const MAX_MEMBERS: usize = 10;
+
+//Basic NEAR amounts as constants
+const ZERO_NEAR: NearToken = NearToken::from_yoctonear(0);
+
+pub type TokenId = String;
+
+//the details of the sale that the market passes in when paying a royalty
+#[derive(Serialize, Deserialize, NearSchema, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct SaleContext {
+    //nft contract where the token was minted
+    pub nft_contract_id: AccountId,
+    //the account that bought the token
+    pub buyer_id: AccountId,
+    //the price the token was sold for
+    pub price: NearToken,
+}
+
+//the member of the splitter and how much of every royalty it's paid
+#[derive(Serialize, Deserialize, NearSchema)]
+#[serde(crate = "near_sdk::serde")]
+pub struct JsonMember {
+    pub account_id: AccountId,
+    //the member's weight. Every royalty is split in proportion to the weights
+    pub weight: u32,
+}
+
+//the method on the market that the splitter calls to be notified of royalties
+#[ext_contract(ext_market)]
+trait ExtMarket {
+    fn register_royalty_receiver(&mut self);
+}
+
+//main contract struct to store all the information
+#[near_bindgen]
+#[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
+#[borsh(crate = "near_sdk::borsh")]
+pub struct Contract {
+    //the owner of the splitter. The owner manages the members and is paid the rounding left over by every split
+    pub owner_id: AccountId,
+
+    //the members that royalties are split between and their weights
+    pub members: UnorderedMap<AccountId, u32>,
+
+    //the total amount of royalties the splitter has received
+    pub total_received: NearToken,
+}
+
+/// Helper structure to for keys of the persistent collections.
+#[derive(BorshStorageKey, BorshSerialize)]
+#[borsh(crate = "near_sdk::borsh")]
+pub enum StorageKey {
+    Members,
+}
+
+#[near_bindgen]
+impl Contract {
+    //initialize the splitter with its owner and the members royalties are split between
+    #[init]
+    pub fn new(owner_id: AccountId, members: HashMap<AccountId, u32>) -> Self {
+        let mut this = Self {
+            owner_id,
+            members: UnorderedMap::new(StorageKey::Members),
+            total_received: ZERO_NEAR,
+        };
+        this.internal_set_members(members);
+        this
+    }
+
+    //replace the members and their weights. Only the owner can call this
+    #[payable]
+    pub fn set_members(&mut self, members: HashMap<AccountId, u32>) {
+        //assert that the user has attached exactly 1 yoctoNEAR (for security reasons)
+        assert_one_yocto();
+        self.assert_owner();
+        self.internal_set_members(members);
+    }
+
+    //register the splitter with a market so that royalties are paid through on_royalty_received.
+    //the attached deposit is passed on to the market to pay for the storage of the registration
+    #[payable]
+    pub fn register_with_market(&mut self, market_id: AccountId) -> Promise {
+        self.assert_owner();
+        ext_market::ext(market_id)
+            .with_attached_deposit(env::attached_deposit())
+            .with_static_gas(GAS_FOR_REGISTER)
+            .register_royalty_receiver()
+    }
+
+    //called by a market when it pays a royalty to the splitter. The attached deposit is split between the members
+    //in proportion to their weights and the rounding left over goes to the owner
+    #[payable]
+    pub fn on_royalty_received(&mut self, token_id: TokenId, amount: NearToken, sale_context: SaleContext) {
+        //only what was actually attached is split, whatever amount the caller passed in
+        let received = env::attached_deposit();
+        self.total_received = self.total_received.saturating_add(received);
+        log!(
+            "Received {} (reported {}) from {} for token {} on {} bought by {} for {}",
+            received,
+            amount,
+            env::predecessor_account_id(),
+            token_id,
+            sale_context.nft_contract_id,
+            sale_context.buyer_id,
+            sale_context.price
+        );
+
+        let members: HashMap<AccountId, u32> = self.members.iter().collect();
+        for (account_id, share) in split_by_weight(received, &members, &self.owner_id) {
+            Promise::new(account_id).transfer(share);
+        }
+    }
+
+    /// views
+    //get the owner of the splitter
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    //get the members royalties are split between and their weights
+    pub fn get_members(&self) -> Vec<JsonMember> {
+        self.members
+            .iter()
+            .map(|(account_id, weight)| JsonMember { account_id, weight })
+            .collect()
+    }
+
+    //get the total amount of royalties the splitter has received
+    pub fn get_total_received(&self) -> NearToken {
+        self.total_received
+    }
+}
+
+impl Contract {
+    //make sure the predecessor is the owner of the splitter
+    fn assert_owner(&self) {
+        assert_eq!(
+            env::predecessor_account_id(),
+            self.owner_id,
+            "Only the owner can call this method"
+        );
+    }
+
+    //replace the members after making sure there are between 1 and MAX_MEMBERS of them and every weight is above 0
+    fn internal_set_members(&mut self, members: HashMap<AccountId, u32>) {
+        assert!(!members.is_empty(), "Must have at least 1 member");
+        assert!(
+            members.len() <= MAX_MEMBERS,
+            "Cannot have more than {} members",
+            MAX_MEMBERS
+        );
+        for (account_id, weight) in members.iter() {
+            assert!(*weight > 0, "Weight for {} must be greater than 0", account_id);
+        }
+
+        self.members.clear();
+        for (account_id, weight) in members.iter() {
+            self.members.insert(account_id, weight);
+        }
+    }
+}
+
+//split an amount between the members in proportion to their weights. Every share is rounded down and the rounding
+//left over is added to the owner's share, so the shares always add up to the amount. Empty shares are left out
+pub(crate) fn split_by_weight(
+    amount: NearToken,
+    members: &HashMap<AccountId, u32>,
+    owner_id: &AccountId,
+) -> HashMap<AccountId, NearToken> {
+    let total_weight: u128 = members.values().map(|weight| *weight as u128).sum();
+    let mut shares = HashMap::new();
+    if total_weight == 0 {
+        shares.insert(owner_id.clone(), amount);
+        return shares;
+    }
+
+    let mut remainder = amount;
+    for (account_id, weight) in members.iter() {
+        //amount * weight / total_weight, split up so it can't overflow
+        let weight = *weight as u128;
+        let yocto = amount.as_yoctonear();
+        let share = NearToken::from_yoctonear(
+            yocto / total_weight * weight + yocto % total_weight * weight / total_weight,
+        );
+        remainder = remainder.saturating_sub(share);
+        shares.insert(account_id.clone(), share);
+    }
+
+    let owner_share = shares.entry(owner_id.clone()).or_insert(ZERO_NEAR);
+    *owner_share = owner_share.saturating_add(remainder);
+    shares.retain(|_, share| !share.is_zero());
+    shares
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::accounts;
+
+    #[test]
+    fn test_split_by_weight() {
+        let members = HashMap::from([(accounts(1), 3), (accounts(2), 1)]);
+        let shares = split_by_weight(NearToken::from_yoctonear(1000), &members, &accounts(0));
+        assert_eq!(shares.get(&accounts(1)), Some(&NearToken::from_yoctonear(750)));
+        assert_eq!(shares.get(&accounts(2)), Some(&NearToken::from_yoctonear(250)));
+        assert_eq!(shares.get(&accounts(0)), None);
+    }
+
+    #[test]
+    fn test_split_by_weight_gives_rounding_to_owner() {
+        let members = HashMap::from([(accounts(1), 1), (accounts(2), 1), (accounts(3), 1)]);
+        let shares = split_by_weight(NearToken::from_yoctonear(100), &members, &accounts(0));
+        assert_eq!(shares.get(&accounts(1)), Some(&NearToken::from_yoctonear(33)));
+        assert_eq!(shares.get(&accounts(0)), Some(&NearToken::from_yoctonear(1)));
+        let total: u128 = shares.values().map(|share| share.as_yoctonear()).sum();
+        assert_eq!(total, 100);
+    }
+
+    #[test]
+    fn test_split_by_weight_does_not_overflow() {
+        let members = HashMap::from([(accounts(1), u32::MAX), (accounts(2), 1)]);
+        let amount = NearToken::from_yoctonear(u128::MAX);
+        let shares = split_by_weight(amount, &members, &accounts(0));
+        let total: u128 = shares.values().map(|share| share.as_yoctonear()).sum();
+        assert_eq!(total, u128::MAX);
+    }
+}